@@ -1,4 +1,29 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+
+use crate::crispr::DetectionParams;
+use crate::fasta::AmbiguityPolicy;
+
+/// How arrays are found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Mode {
+    /// Find arrays of three or more repeats from repeated k-mers
+    Denovo,
+    /// Find copies of the repeats in --repeat-library, including two-repeat arrays and orphan repeats
+    Library,
+    /// Both, keeping de novo calls where they overlap library calls
+    Both,
+}
+
+/// What to do with IUPAC ambiguity codes other than N in the assembly
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Ambiguity {
+    /// Keep them. They match any base they stand for
+    Keep,
+    /// Replace them with N
+    ToN,
+    /// Stop with an error giving the position of the first one
+    Reject,
+}
 
 /// crisprs (CRISPR in-silico prediction with Rust)
 /// Predict CRISPR arrays in assemblies
@@ -11,6 +36,68 @@ pub struct Opts {
     /// outprefix
     #[clap(short, long)]
     outprefix: String,
+    /// Detection mode
+    #[clap(long, value_enum, default_value_t = Mode::Denovo)]
+    mode: Mode,
+    /// FASTA of known CRISPR repeats, in their transcribed orientation. Needed for library mode.
+    /// When given, de novo arrays are also named after the library repeat they match
+    #[clap(long)]
+    repeat_library: Option<String>,
+    /// Comma separated sizes of the seed k-mers used to find candidate repeats.
+    /// Detection runs at each size and overlapping calls are merged
+    #[clap(long, value_delimiter = ',', default_values_t = DetectionParams::default().kmer_sizes)]
+    kmer_sizes: Vec<usize>,
+    /// Minimum number of repeats to call an array
+    #[clap(long, default_value_t = DetectionParams::default().min_reps)]
+    min_repeats: usize,
+    /// Minimum repeat length
+    #[clap(long, default_value_t = DetectionParams::default().min_rep_size)]
+    min_repeat_size: usize,
+    /// Maximum repeat length
+    #[clap(long, default_value_t = DetectionParams::default().max_rep_size)]
+    max_repeat_size: usize,
+    /// Minimum spacer length
+    #[clap(long, default_value_t = DetectionParams::default().min_spacer_size)]
+    min_spacer_size: usize,
+    /// Maximum spacer length
+    #[clap(long, default_value_t = DetectionParams::default().max_spacer_size)]
+    max_spacer_size: usize,
+    /// Maximum proportion difference in repeat lengths within an array
+    #[clap(long, default_value_t = DetectionParams::default().max_rep_len_dev)]
+    max_repeat_len_dev: f32,
+    /// Maximum proportion difference in spacer lengths within an array
+    #[clap(long, default_value_t = DetectionParams::default().max_spacer_len_dev)]
+    max_spacer_len_dev: f32,
+    /// Maximum mismatches between a repeat copy and the consensus repeat
+    #[clap(long, default_value_t = DetectionParams::default().max_mismatches)]
+    max_mismatches: usize,
+    /// Maximum inserted or deleted bases between a repeat copy and the consensus repeat
+    #[clap(long, default_value_t = DetectionParams::default().max_indels)]
+    max_indels: usize,
+    /// Longest insertion, such as an IS element, to join array fragments with the same repeat across
+    #[clap(long, default_value_t = DetectionParams::default().max_insertion_size)]
+    max_insertion_size: usize,
+    /// Bases upstream of the first repeat to extract as the leader
+    #[clap(long, default_value_t = 200)]
+    leader_length: usize,
+    /// Only report arrays with at least this confidence score (0-1)
+    #[clap(long, default_value_t = 0.0)]
+    min_score: f64,
+    /// Don't seed repeats from soft-masked (lowercase) bases
+    #[clap(long)]
+    skip_masked: bool,
+    /// Flag arrays that overlap soft-masked (lowercase) bases
+    #[clap(long)]
+    report_masked: bool,
+    /// How to handle IUPAC ambiguity codes in the assembly
+    #[clap(long, value_enum, default_value_t = Ambiguity::Keep)]
+    ambiguity: Ambiguity,
+    /// Treat every contig as circular. Contigs with circular=true in their header are always treated as circular
+    #[clap(long)]
+    circular: bool,
+    /// Also write candidate arrays that failed validation, with the reason, to <outprefix>_rejected.tsv
+    #[clap(long)]
+    debug: bool,
 }
 
 impl Opts {
@@ -22,6 +109,59 @@ impl Opts {
         &self.outprefix
     }
 
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn repeat_library(&self) -> Option<&str> {
+        self.repeat_library.as_deref()
+    }
+
+    pub fn detection_params(&self) -> DetectionParams {
+        DetectionParams {
+            kmer_sizes: self.kmer_sizes.clone(),
+            min_reps: self.min_repeats,
+            min_rep_size: self.min_repeat_size,
+            max_rep_size: self.max_repeat_size,
+            min_spacer_size: self.min_spacer_size,
+            max_spacer_size: self.max_spacer_size,
+            max_rep_len_dev: self.max_repeat_len_dev,
+            max_spacer_len_dev: self.max_spacer_len_dev,
+            max_mismatches: self.max_mismatches,
+            max_indels: self.max_indels,
+            max_insertion_size: self.max_insertion_size,
+            skip_masked: self.skip_masked,
+        }
+    }
+
+    pub fn leader_length(&self) -> usize {
+        self.leader_length
+    }
+
+    pub fn min_score(&self) -> f64 {
+        self.min_score
+    }
+
+    pub fn report_masked(&self) -> bool {
+        self.report_masked
+    }
+
+    pub fn ambiguity_policy(&self) -> AmbiguityPolicy {
+        match self.ambiguity {
+            Ambiguity::Keep => AmbiguityPolicy::Keep,
+            Ambiguity::ToN => AmbiguityPolicy::ToN,
+            Ambiguity::Reject => AmbiguityPolicy::Reject,
+        }
+    }
+
+    pub fn circular(&self) -> bool {
+        self.circular
+    }
+
+    pub fn debug(&self) -> bool {
+        self.debug
+    }
+
     pub fn parse_args() -> Self {
         Self::parse()
     }
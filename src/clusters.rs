@@ -13,7 +13,7 @@ pub struct Clusters <T: Hash + Eq + Clone> {
 
 // constructors
 impl<T: Hash + Eq + Clone> Clusters<T> {
-    fn new() -> Self {
+    pub fn new() -> Self {
         Self {
             membership: HashMap::new(),
             clusters: HashMap::new(),
@@ -54,18 +54,18 @@ impl<T: Hash + Eq + Clone> Clusters<T> {
     fn add_to_cluster_with(&mut self, new_member: &T, existing_member: &T){
         let cluster_number = self.membership.get(existing_member)
             .expect("This method should only be called after determining this member is already present");
-        self.clusters.entry(cluster_number.clone())
+        self.clusters.entry(*cluster_number)
             .and_modify(|clus| clus.add(new_member.clone()));
-        self.membership.insert(new_member.clone(), cluster_number.clone());
+        self.membership.insert(new_member.clone(), *cluster_number);
     }
 
     fn merge_clusters_containing_members(&mut self, member_a: &T, member_b: &T) {
         let clus_num_a = self.membership.get(member_a)
-            .expect("This method should only be called after determining this member is already present")
-            .clone();
+            .copied()
+            .expect("This method should only be called after determining this member is already present");
         let clus_num_b = self.membership.get(member_b)
-            .expect("This method should only be called after determining this member is already present")
-            .clone();
+            .copied()
+            .expect("This method should only be called after determining this member is already present");
 
         // remove clus_b and update memberships
         let clus_b= self.clusters.remove(&clus_num_b)
@@ -81,6 +81,19 @@ impl<T: Hash + Eq + Clone> Clusters<T> {
             .merge(clus_b);
     }
 
+    // Add a node with no edges. Does nothing if the node is already in a cluster
+    pub fn add_node(&mut self, node: T) {
+        if !self.membership.contains_key(&node) {
+            self.add_cluster(Cluster::new(node))
+        }
+    }
+
+    pub fn get_clusters(&self) -> Vec<Vec<T>> {
+        self.clusters.values()
+            .map(|c| c.get_members())
+            .collect()
+    }
+
     pub fn add_edge(&mut self, node_a: T, node_b: T) {
         // check if either is already in clusters
         let membership = [self.membership.contains_key(&node_a), self.membership.contains_key(&node_b)];
@@ -93,6 +106,12 @@ impl<T: Hash + Eq + Clone> Clusters<T> {
     }
 }
 
+impl<T: Hash + Eq + Clone> Default for Clusters<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 // define == behaviour
 impl <T: Hash + Eq + Clone> PartialEq for Clusters<T> {
     fn eq(&self, other: &Self) -> bool {
@@ -100,10 +119,6 @@ impl <T: Hash + Eq + Clone> PartialEq for Clusters<T> {
         let b: HashSet<&Cluster<T>> = HashSet::from_iter(other.clusters.values());
         a == b
     }
-
-    fn ne(&self, other: &Self) -> bool {
-        !self.eq(other)
-    }
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
@@ -155,13 +170,23 @@ mod tests {
         let node_c = 3;
         let node_d = 4;
         let mut result = Clusters::from_node_pair(&node_a, &node_b);
-        result.add_edge(node_c.clone(), node_d.clone());
-        result.add_edge(node_a.clone(), node_d.clone());
+        result.add_edge(node_c, node_d);
+        result.add_edge(node_a, node_d);
 
         let mut expected = Clusters::from_node_pair(&node_a, &node_b);
-        expected.add_edge(node_b.clone(), node_c.clone());
-        expected.add_edge(node_c.clone(), node_d.clone());
+        expected.add_edge(node_b, node_c);
+        expected.add_edge(node_c, node_d);
 
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn clusters_add_node_makes_singleton() {
+        let mut result = Clusters::from_node_pair(&1, &2);
+        result.add_node(3);
+        result.add_node(1);
+        let mut clusters = result.get_clusters();
+        clusters.sort();
+        assert_eq!(clusters, vec![vec![1, 2], vec![3]]);
+    }
 }
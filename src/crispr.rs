@@ -1,32 +1,343 @@
-use std::f32::MIN;
+use std::collections::HashMap;
+use std::fmt;
 
-use crate::fasta::{Seq, Fasta};
+use anyhow::{anyhow, Result};
+
+use crate::clusters::Clusters;
+use crate::fasta::{complement, Seq, Fasta};
 use crate::kmer::{KmerTable, KmerLocs};
+use crate::slice::SeqSlice;
+
+mod extend;
+mod approximate;
+mod boundary;
+pub mod circular;
+pub mod consensus;
+pub mod evidence;
+mod filter;
+pub mod flags;
+pub mod interruption;
+pub mod leader;
+pub mod library;
+pub mod orientation;
+pub mod overlap;
+pub mod params;
+pub mod repeat;
+mod terminal;
+pub mod validate;
 
+pub use circular::find_crisprs_circular_with_rejections;
+pub use consensus::{ColumnConservation, ConservationProfile};
+pub use evidence::EvidenceLevel;
+pub use flags::{ArrayFlag, Side};
+pub use interruption::Interruption;
+pub use leader::Leader;
+pub use library::{LibraryRepeat, RepeatLibrary};
+pub use orientation::Strand;
+pub use overlap::MergedCall;
+pub use params::DetectionParams;
+pub use repeat::{Repeat, Variant};
+pub use validate::{Rejection, RejectReason};
 
-// Settings for determining valid arrays
-const MIN_REPS: u32 = 3; // minimum number of repeats to call an array
-const MIN_REP_SIZE: u32 = 20;
-const MAX_REP_SIZE: u32 = 70;
-const MIN_SPACER_SIZE: u32 = 20;
-const MAX_SPACER_SIZE: u32 = 70;
-const MAX_REP_LEN_DEV: f32 = 0.1; // maximum proportion difference in repeat lengths in array
-const MAX_SPACER_LEN_DEV: f32 = 0.1; // maximum proportion difference in spacer lengths in array
 
 
+#[derive(Debug, Clone)]
 pub struct CRISPRArray {
-    repeats: Vec<Seq>,
+    repeats: Vec<Repeat>,
     spacers: Vec<Seq>,
     source_name: String,
     location: [usize; 2],
+    consensus: Seq,
+    conservation: ConservationProfile,
+    strand: Strand,
+    orientation: Option<Strand>,
+    flags: Vec<ArrayFlag>,
+    confidence: f64,
+    evidence: EvidenceLevel,
+    supporting_k: Vec<usize>,
+    library_match: Option<String>,
+    leader: Option<Leader>,
+    interruptions: Vec<Interruption>,
+    boundary_shift: [isize; 2],
+    merged_calls: Vec<MergedCall>,
+}
+
+// constructors
+impl CRISPRArray {
+    /// Build an array from the start positions of equal length repeats in `source_seq`.
+    /// Everything between consecutive repeats is taken as a spacer.
+    pub fn from_repeat_starts<'a>(source_seq: impl Into<SeqSlice<'a>>, source_name: &str, starts: &[usize], rep_len: usize) -> Result<Self> {
+        let source_seq = source_seq.into();
+        let repeats = starts.iter()
+            .map(|start| Ok(Repeat::exact(source_seq.slice(*start, start + rep_len)?.to_seq(), *start)))
+            .collect::<Result<Vec<Repeat>>>()?;
+        Self::from_repeats(source_seq, source_name, repeats)
+    }
+
+    /// Build an array from repeat copies in `source_seq`, ordered by position.
+    /// Everything between consecutive repeats is taken as a spacer. The consensus
+    /// repeat is computed from the copies and each copy's variants are set relative to it.
+    pub fn from_repeats<'a>(source_seq: impl Into<SeqSlice<'a>>, source_name: &str, repeats: Vec<Repeat>) -> Result<Self> {
+        let source_seq = source_seq.into();
+        if repeats.len() < 2 {
+            return Err(anyhow!("an array needs at least two repeats"))
+        }
+        let mut spacers = Vec::<Seq>::with_capacity(repeats.len() - 1);
+        for pair in repeats.windows(2) {
+            if pair[1].start() <= pair[0].end() {
+                return Err(anyhow!("repeats at {} and {} overlap", pair[0].start(), pair[1].start()))
+            }
+            spacers.push(source_seq.slice(pair[0].end(), pair[1].start())?.to_seq());
+        }
+        let location = [
+            repeats[0].start(),
+            repeats.last().expect("Checked there are at least two repeats").end()
+        ];
+        Self::assemble(repeats, spacers, source_name, location, Strand::Forward)
+    }
+
+    /// An orphan repeat: an "array" of one repeat and no spacers
+    pub fn from_single_repeat(source_name: &str, repeat: Repeat) -> Result<Self> {
+        let location = [repeat.start(), repeat.end()];
+        Self::assemble(vec![repeat], Vec::new(), source_name, location, Strand::Forward)
+    }
+
+    // Compute the consensus of the repeats and set each copy's variants relative to it
+    fn assemble(mut repeats: Vec<Repeat>, spacers: Vec<Seq>, source_name: &str, location: [usize; 2], strand: Strand) -> Result<Self> {
+        let consensus = consensus::build(&repeats);
+        for (rep, variants) in repeats.iter_mut().zip(consensus.variants) {
+            rep.set_variants(variants);
+        }
+        Ok(Self {
+            repeats,
+            spacers,
+            source_name: source_name.to_string(),
+            location,
+            consensus: Seq::from_dna(String::from_utf8(consensus.seq)?)?,
+            conservation: consensus.profile,
+            strand,
+            orientation: None,
+            flags: Vec::new(),
+            confidence: 0.0,
+            evidence: EvidenceLevel::One,
+            supporting_k: Vec::new(),
+            library_match: None,
+            leader: None,
+            interruptions: Vec::new(),
+            boundary_shift: [0, 0],
+            merged_calls: Vec::new(),
+        })
+    }
+
+    /// The same array read from the other strand: repeats and spacers are
+    /// reverse complemented and listed in the opposite order. Coordinates stay
+    /// on the forward strand.
+    pub fn reverse_complement(&self) -> Self {
+        let repeats = self.repeats.iter().rev()
+            .map(|r| r.reverse_complement())
+            .collect();
+        let spacers = self.spacers.iter().rev()
+            .map(|s| s.rev_comp())
+            .collect();
+        let mut rc = Self::assemble(repeats, spacers, &self.source_name, self.location, self.strand.opposite())
+            .expect("Reverse complement of a valid array is valid");
+        rc.orientation = self.orientation;
+        rc.flags = self.flags.clone();
+        rc.supporting_k = self.supporting_k.clone();
+        rc.library_match = self.library_match.clone();
+        rc.leader = self.leader.clone();
+        rc.interruptions = self.interruptions.iter().rev()
+            .map(|i| i.reverse_complement(self.spacers.len()))
+            .collect();
+        rc.boundary_shift = [self.boundary_shift[1], self.boundary_shift[0]];
+        rc.merged_calls = self.merged_calls.clone();
+        rc.update_confidence();
+        rc
+    }
+
+    // Convert an array found in the reverse complement of a sequence of length
+    // `source_len` to forward strand coordinates
+    fn into_forward_coords(mut self, source_len: usize) -> Self {
+        for rep in self.repeats.iter_mut() {
+            let start = source_len - rep.end();
+            rep.set_start(start);
+        }
+        self.location = [source_len - self.location[1], source_len - self.location[0]];
+        self.interruptions = self.interruptions.into_iter()
+            .map(|i| i.into_forward_coords(source_len))
+            .collect();
+        self.merged_calls = self.merged_calls.into_iter()
+            .map(|m| m.into_forward_coords(source_len))
+            .collect();
+        self.strand = self.strand.opposite();
+        self
+    }
+
+    // Convert coordinates in a window of a circular sequence starting at
+    // `offset` back to positions modulo the sequence length. Ends are
+    // exclusive so stay in 1..=len
+    fn into_circular_coords(mut self, offset: usize, source_len: usize) -> Self {
+        for rep in self.repeats.iter_mut() {
+            let start = (rep.start() + offset) % source_len;
+            rep.set_start(start);
+        }
+        self.location = circular_location(self.location, offset, source_len);
+        self.interruptions = self.interruptions.into_iter()
+            .map(|i| i.into_circular_coords(offset, source_len))
+            .collect();
+        self.merged_calls = self.merged_calls.into_iter()
+            .map(|m| m.into_circular_coords(offset, source_len))
+            .collect();
+        self
+    }
+}
+
+// Start and (exclusive) end in a window of a circular sequence starting at
+// `offset`, as positions modulo the sequence length
+fn circular_location([start, end]: [usize; 2], offset: usize, source_len: usize) -> [usize; 2] {
+    [(start + offset) % source_len, (end + offset - 1) % source_len + 1]
 }
 
 // methods
 impl CRISPRArray {
+    pub fn repeats(&self) -> &[Repeat] {
+        &self.repeats
+    }
+
+    pub fn spacers(&self) -> &[Seq] {
+        &self.spacers
+    }
+
+    pub fn source_name(&self) -> &str {
+        &self.source_name
+    }
+
+    /// Name of the source sequence: the first word of its FASTA header, as
+    /// other tools cut headers at the first space
+    pub fn contig(&self) -> &str {
+        self.source_name.split_whitespace().next().unwrap_or(&self.source_name)
+    }
+
+    /// 0-based start and (exclusive) end of the array on the forward strand of the source sequence.
+    /// For arrays spanning the origin of a circular sequence the end is before the start
+    pub fn location(&self) -> [usize; 2] {
+        self.location
+    }
+
+    pub fn spans_origin(&self) -> bool {
+        self.location[1] < self.location[0]
+    }
+
+    pub fn flags(&self) -> &[ArrayFlag] {
+        &self.flags
+    }
+
+    /// Whether any flag suggests the array may be missing repeats
+    pub fn possibly_incomplete(&self) -> bool {
+        self.flags.iter().any(|f| f.possibly_incomplete())
+    }
+
+    /// Confidence in the call between 0 and 1. See `evidence::confidence`
+    pub fn confidence(&self) -> f64 {
+        self.confidence
+    }
+
+    pub fn evidence_level(&self) -> EvidenceLevel {
+        self.evidence
+    }
+
+    /// Seed k-mer sizes the array was found with
+    pub fn supporting_k(&self) -> &[usize] {
+        &self.supporting_k
+    }
+
+    /// Name of the library repeat the array matches, if a repeat library was used
+    pub fn library_match(&self) -> Option<&str> {
+        self.library_match.as_deref()
+    }
+
+    /// Bases added to the 5' and 3' end of every repeat by boundary optimisation.
+    /// Negative when bases were moved from the repeats to the spacers
+    pub fn boundary_shift(&self) -> [isize; 2] {
+        self.boundary_shift
+    }
+
+    /// Overlapping calls of the same locus that were merged into this one.
+    /// See `overlap::resolve_overlaps`
+    pub fn merged_calls(&self) -> &[MergedCall] {
+        &self.merged_calls
+    }
+
+    /// Large insertions within the array. See `interruption::join_interrupted`
+    pub fn interruptions(&self) -> &[Interruption] {
+        &self.interruptions
+    }
+
+    /// Sequence upstream of the first repeat, if extracted. See `leader::add_leaders`
+    pub fn leader(&self) -> Option<&Leader> {
+        self.leader.as_ref()
+    }
+
+    // Record that the array was also found with the k-mer sizes in `ks`
+    fn add_supporting_k(&mut self, ks: &[usize]) {
+        self.supporting_k.extend(ks);
+        self.supporting_k.sort();
+        self.supporting_k.dedup();
+    }
+
+    pub(crate) fn update_confidence(&mut self) {
+        self.confidence = evidence::confidence(self);
+        self.evidence = EvidenceLevel::from_score(self.confidence);
+    }
+
+    /// Typical distance from the start of one repeat to the start of the next.
+    /// Spacers containing assembly gaps are ignored. For an orphan repeat this is its length
+    pub fn period(&self) -> usize {
+        let mut rep_lens: Vec<usize> = self.repeats.iter().map(|r| r.len()).collect();
+        rep_lens.sort();
+        let mut spacer_lens: Vec<usize> = self.spacers.iter()
+            .filter(|s| !s.contains_n())
+            .map(|s| s.len())
+            .collect();
+        if spacer_lens.is_empty() {
+            spacer_lens = self.spacers.iter().map(|s| s.len()).collect();
+        }
+        spacer_lens.sort();
+        rep_lens[rep_lens.len() / 2] + spacer_lens.get(spacer_lens.len() / 2).unwrap_or(&0)
+    }
+
+    pub fn consensus(&self) -> &Seq {
+        &self.consensus
+    }
+
+    /// Per-position conservation of the repeats, in consensus coordinates
+    pub fn conservation(&self) -> &ConservationProfile {
+        &self.conservation
+    }
+
+    /// Strand the repeats and spacers are read from. Repeats and spacers are
+    /// listed in the order they appear on this strand.
+    pub fn strand(&self) -> Strand {
+        self.strand
+    }
+
+    /// Predicted transcriptional orientation, if there was enough evidence.
+    /// When predicted, the array is reported on this strand so the first repeat
+    /// is at the leader end.
+    pub fn orientation(&self) -> Option<Strand> {
+        self.orientation
+    }
+
+    pub fn id(&self) -> String {
+        format!("{}:{}-{}", self.contig(), self.location[0], self.location[1])
+    }
+
     pub fn to_fasta(&self, header: &str) -> String {
         let mut seq_list = Vec::<String>::with_capacity(self.repeats.len()*2);
 
-        seq_list.push(format!(">{header}\n"));
+        seq_list.push(format!(">{header} consensus={} confidence={:.2} evidence_level={} supporting_k={} library_match={} interruptions={} flags={}\n",
+            self.consensus, self.confidence, self.evidence, format_list(&self.supporting_k), self.library_match.as_deref().unwrap_or("-"),
+            format_list(&self.interruptions), format_list(&self.flags)));
         for (rep, sp) in self.repeats.iter()
             .zip(self.spacers.iter()) {
                 seq_list.push(rep.to_string());
@@ -40,43 +351,207 @@ impl CRISPRArray {
     }
 
     pub fn to_table(&self) -> String {
-        let mut seq_rows = Vec::<String>::with_capacity(self.repeats.len());
+        let mut seq_rows = Vec::<String>::with_capacity(self.repeats.len() + 1);
+        seq_rows.push(format!("#consensus\t{}\n", self.consensus));
+        seq_rows.push(format!("#confidence\t{:.2}\tevidence_level\t{}\n", self.confidence, self.evidence));
+        seq_rows.push(format!("#flags\t{}\n", format_list(&self.flags)));
         for (rep, sp) in self.repeats.iter()
             .zip(self.spacers.iter()) {
-                seq_rows.push(format!("{}\t{}\n", rep.to_string(), sp.to_string()));
+                seq_rows.push(format!("{rep}\t{sp}\t{}\n", format_variants(rep)));
         }
-        seq_rows.push(
-            format!("{}\n", self.repeats
-                .last()
-                .expect("There should always be one more repeat than spacer in an array")
-                .to_string()
-                )
-            );
+        let last = self.repeats
+            .last()
+            .expect("There should always be one more repeat than spacer in an array");
+        seq_rows.push(format!("{last}\t\t{}\n", format_variants(last)));
         seq_rows.join("")
     }
+
+    pub fn summary_header() -> String {
+        ["array_id", "contig", "start", "end", "strand", "orientation", "repeats", "spacers", "degenerate_repeats", "consensus_repeat", "confidence", "evidence_level", "supporting_k", "library_match", "leader_at_content", "leader_similarity", "interruptions", "boundary_shift", "merged_calls", "flags"].join("\t")
+    }
+
+    /// One tab separated line describing the array. Columns match `summary_header`
+    pub fn to_summary(&self) -> String {
+        format!("{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{:.2}\t{}\t{}\t{}\t{}\t{}\t{}\t{:+},{:+}\t{}\t{}",
+            self.id(),
+            self.contig(),
+            self.location[0],
+            self.location[1],
+            self.strand,
+            self.orientation.map_or("unknown".to_string(), |o| o.to_string()),
+            self.repeats.len(),
+            self.spacers.len(),
+            self.repeats.iter().filter(|r| r.is_degenerate()).count(),
+            self.consensus,
+            self.confidence,
+            self.evidence,
+            format_list(&self.supporting_k),
+            self.library_match.as_deref().unwrap_or("-"),
+            self.leader.as_ref().map_or("-".to_string(), |l| format!("{:.2}", l.at_content())),
+            self.leader.as_ref().and_then(|l| l.similarity()).map_or("-".to_string(), |s| format!("{s:.2}")),
+            format_list(&self.interruptions),
+            self.boundary_shift[0],
+            self.boundary_shift[1],
+            format_list(&self.merged_calls),
+            format_list(&self.flags),
+        )
+    }
+}
+
+
+// Comma separated items, or "-" if there are none
+fn format_list<T: fmt::Display>(items: &[T]) -> String {
+    match items.is_empty() {
+        true => "-".to_string(),
+        false => items.iter()
+            .map(|i| i.to_string())
+            .collect::<Vec<String>>()
+            .join(","),
+    }
+}
+
+fn format_variants(rep: &Repeat) -> String {
+    let variants = match rep.is_exact() {
+        true => "-".to_string(),
+        false => format_list(rep.variants()),
+    };
+    match rep.is_degenerate() {
+        true => format!("degenerate:{variants}"),
+        false => variants,
+    }
+}
+
+
+/// Find CRISPR arrays in a sequence, or a view of one. Coordinates are
+/// relative to the start of the view. Fails if `params` are inconsistent.
+pub fn find_crisprs<'a>(source: impl Into<SeqSlice<'a>>, source_name: &str, params: &DetectionParams) -> Result<Vec<CRISPRArray>> {
+    Ok(find_crisprs_with_rejections(source, source_name, params)?.0)
+}
+
+/// As `find_crisprs`, but also returns the candidate arrays that failed validation
+/// along with the reason each was rejected.
+///
+/// Both strands are searched. Each array is reported on its predicted
+/// transcriptional strand where there is enough evidence to predict it.
+pub fn find_crisprs_with_rejections<'a>(source: impl Into<SeqSlice<'a>>, source_name: &str, params: &DetectionParams) -> Result<(Vec<CRISPRArray>, Vec<Rejection>)> {
+    params.validate()?;
+    let source_seq = source.into();
+    let (mut arrays, rejected) = find_on_both_strands(source_seq, source_name, params, None);
+    for array in arrays.iter_mut() {
+        array.flags.extend(flags::truncation_flags(array, source_seq.len()));
+    }
+    Ok((arrays, rejected))
 }
 
+// Arrays from both strands, with overlapping calls resolved, before flagging
+// anything that depends on the sequence being linear. `circular_len` is the
+// length of the circular contig `source_seq` was taken from, if any
+fn find_on_both_strands(source_seq: SeqSlice, source_name: &str, params: &DetectionParams, circular_len: Option<usize>) -> (Vec<CRISPRArray>, Vec<Rejection>) {
+    let source_bytes = source_seq.to_bytes();
+    let rev_bytes: Vec<u8> = source_bytes.iter().rev().map(|b| complement(*b)).collect();
+    let (forward, mut rejected) = find_on_strand(source_seq, &source_bytes, source_name, params, circular_len);
+    let (reverse, rev_rejected) = find_on_strand(source_seq.rev_comp(), &rev_bytes, source_name, params, circular_len);
+    let reverse: Vec<CRISPRArray> = reverse.into_iter()
+        .map(|a| a.into_forward_coords(source_seq.len()))
+        .collect();
+    rejected.extend(rev_rejected.into_iter()
+        .map(|r| {
+            let (array, reason) = r.into_parts();
+            Rejection::new(array.into_forward_coords(source_seq.len()), reason)
+        })
+    );
 
-pub fn find_crisprs(source_seq: &Seq, source_name: &str, k: usize) -> Option<u32> { // Option<CRISPRArray> {
-    let kt = KmerTable::from_seq(source_seq, k);
-    let candidate_kmers = match get_candidate_kmers(kt) {
+    let mut arrays = orient(forward, &source_bytes);
+    arrays.extend(orient(reverse, &source_bytes));
+    for array in arrays.iter_mut() {
+        array.flags.extend(flags::gap_flags(array, &source_bytes));
+        array.update_confidence();
+    }
+    (overlap::resolve_overlaps(arrays), rejected)
+}
+
+// Predict each array's orientation and flip it onto the predicted strand
+fn orient(arrays: Vec<CRISPRArray>, source: &[u8]) -> Vec<CRISPRArray> {
+    arrays.into_iter()
+        .map(|mut a| {
+            a.orientation = orientation::predict(&a, source);
+            match a.orientation {
+                Some(strand) if strand != a.strand => a.reverse_complement(),
+                _ => a,
+            }
+        })
+        .collect()
+}
+
+// `source_bytes` are the bases of `source_seq`, shared with the caller so
+// they're only expanded once per strand
+fn find_on_strand(source_seq: SeqSlice, source_bytes: &[u8], source_name: &str, params: &DetectionParams, circular_len: Option<usize>) -> (Vec<CRISPRArray>, Vec<Rejection>) {
+    let mut candidates: Vec<CRISPRArray> = params.kmer_sizes.iter()
+        .flat_map(|k| candidates_for_k(source_seq, source_name, source_bytes, *k, params))
+        .collect();
+    // Filling in degenerate copies can make different seeds, or seeds of
+    // different sizes, converge
+    candidates.sort_by_key(repeat_bounds);
+    candidates.dedup_by(|dup, kept| {
+        let same = repeat_bounds(dup) == repeat_bounds(kept);
+        if same {
+            kept.add_supporting_k(&dup.supporting_k);
+        }
+        same
+    });
+
+    let mut arrays = Vec::new();
+    let mut rejected = Vec::new();
+    for candidate in candidates {
+        let candidate = boundary::optimise_boundaries(candidate, source_seq, source_bytes, params);
+        match validate::validate(&candidate, params).and_then(|_| filter::filter(&candidate)) {
+            Ok(()) => {
+                let ks = candidate.supporting_k.clone();
+                let shift = candidate.boundary_shift;
+                let mut array = terminal::add_degenerate_terminal_repeats(candidate, source_seq, source_bytes, params);
+                array.add_supporting_k(&ks);
+                array.boundary_shift = shift;
+                array.update_confidence();
+                arrays.push(array)
+            },
+            Err(reason) => rejected.push(Rejection::new(candidate, reason)),
+        }
+    }
+    (interruption::join_interrupted(overlap::resolve_overlaps(arrays), source_seq, source_bytes, params, circular_len), rejected)
+}
+
+// Candidate arrays seeded by k-mers of size `k`, before validation
+fn candidates_for_k(source_seq: SeqSlice, source_name: &str, source_bytes: &[u8], k: usize, params: &DetectionParams) -> Vec<CRISPRArray> {
+    let kt = match params.skip_masked {
+        true => KmerTable::from_slice_unmasked(source_seq, k),
+        false => KmerTable::from_slice(source_seq, k),
+    };
+    let candidate_kmers = match get_candidate_kmers(kt, params) {
         Some(thing) => thing,
-        _ => return None,
+        _ => return Vec::new(),
     };
 
-    // replace with CRISPRArray return
-    Some(5)
-    // for (k, locs) in kmers.iter() {
-    //     if locs.len() >= 9 {
-    //         println!("{}: {:?}", k, locs);
-    //     }
-    // }
+    let mut extended: Vec<extend::ExtendedRepeats> = collapse_shifted_candidates(candidate_kmers, params.max_rep_size).iter()
+        .filter_map(|kl| extend::extend_seed(source_bytes, kl, params.min_rep_size, params.max_rep_size))
+        .collect();
+    // Seeds that weren't grouped can still extend to the same repeats
+    extended.sort_by(|a, b| a.starts.cmp(&b.starts).then(a.len.cmp(&b.len)));
+    extended.dedup();
+
+    extended.iter()
+        .flat_map(|ext| approximate::approximate_repeats(source_seq, source_bytes, ext, params))
+        .filter_map(|run| CRISPRArray::from_repeats(source_seq, source_name, run).ok())
+        .map(|mut a| {
+            a.supporting_k = vec![k];
+            a
+        })
+        .collect()
 }
 
-fn get_candidate_kmers(kt: KmerTable) -> Option<Vec<KmerLocs>> {
+fn get_candidate_kmers(kt: KmerTable, params: &DetectionParams) -> Option<Vec<KmerLocs>> {
     let mut clusters: Vec<KmerLocs> = Vec::new();
     for (k, locs) in kt.iter() {
-        let clus = match find_kmer_loc_clusters(locs) {
+        let clus = match find_kmer_loc_clusters(locs, params) {
             Some(thing) => thing,
             _ => continue,
         };
@@ -85,31 +560,73 @@ fn get_candidate_kmers(kt: KmerTable) -> Option<Vec<KmerLocs>> {
         }
     }
 
+    match clusters.is_empty() {
+        true => None,
+        false => Some(clusters),
+    }
+}
+
+fn repeat_bounds(array: &CRISPRArray) -> Vec<[usize; 2]> {
+    array.repeats.iter()
+        .map(|r| [r.start(), r.end()])
+        .collect()
+}
 
-    // To del
-    let kl = KmerLocs::new( Seq::from_dna("ATCG".to_string()).unwrap(), vec![0usize]);
-    match kl.k().len() {
-        0 => None,
-        _ => Some(vec![kl]),
+/// Every k-mer within a repeat gives its own candidate, each with the same
+/// locations offset by a constant shift. Group those candidates and keep the
+/// leftmost seed of each group so each array is only considered once.
+fn collapse_shifted_candidates(candidates: Vec<KmerLocs>, max_rep_size: usize) -> Vec<KmerLocs> {
+    // Shifted candidates have identical spacing between their locations
+    let mut by_spacing: HashMap<Vec<usize>, Vec<usize>> = HashMap::new();
+    for (i, c) in candidates.iter().enumerate() {
+        let spacing = c.locs().windows(2)
+            .map(|w| w[1] - w[0])
+            .collect();
+        by_spacing.entry(spacing).or_default().push(i);
     }
+
+    let mut clusters = Clusters::new();
+    for members in by_spacing.values_mut() {
+        members.sort_by_key(|i| candidates[*i].locs()[0]);
+        clusters.add_node(members[0]);
+        for pair in members.windows(2) {
+            let shift = candidates[pair[1]].locs()[0] - candidates[pair[0]].locs()[0];
+            match shift < max_rep_size {
+                true => clusters.add_edge(pair[0], pair[1]),
+                false => clusters.add_node(pair[1]),
+            }
+        }
+    }
+
+    let mut collapsed: Vec<KmerLocs> = clusters.get_clusters().iter()
+        .map(|members| {
+            let leftmost = members.iter()
+                .min_by_key(|i| candidates[**i].locs()[0])
+                .expect("Clusters always have at least one member");
+            candidates[*leftmost].clone()
+        })
+        .collect();
+    collapsed.sort_by_key(|c| c.locs()[0]);
+    collapsed
 }
 
-fn find_kmer_loc_clusters(locs: &Vec<usize>) -> Option<Vec<Vec<usize>>> {
+fn find_kmer_loc_clusters(locs: &[usize], params: &DetectionParams) -> Option<Vec<Vec<usize>>> {
+    let min_reps = params.min_reps;
     // initial checks to determine whether to proceed
-    if locs.len() < MIN_REPS as usize {
+    if locs.len() < min_reps {
         return None
     }
     // Set max interval between kmers that could be array
-    let min_max_size = (MIN_REPS * (MAX_REP_SIZE + MAX_SPACER_SIZE)) as usize;
+    let min_max_size = min_reps * (params.max_rep_size + params.max_spacer_size);
     let mut clusters: Vec<Vec<usize>> = Vec::new();
     let mut this_clus = Vec::<usize>::new();
     let mut in_cluster: bool = false; // keep track of whether to start or add to cluster
-    for i in MIN_REPS as usize .. locs.len() {
-        if locs[i] - locs[i - (MIN_REPS - 1) as usize] < min_max_size {
+    for i in (min_reps - 1) .. locs.len() {
+        if locs[i] - locs[i - (min_reps - 1)] < min_max_size {
             if in_cluster {
                 this_clus.push(locs[i])
             } else {
-                let array_start = i - (MIN_REPS - 1) as usize;
+                let array_start = i - (min_reps - 1);
                 this_clus.extend(&locs[array_start ..= i]);
                 in_cluster = true;
             }
@@ -140,17 +657,175 @@ mod tests {
 
     use super::*;
 
+    // Deterministic pseudo-random DNA so tests don't depend on a rng crate
+    pub(crate) const TEST_REPEAT: &str = "GTTTTAGAGCTATGCTGTTTTGAATGGTCCCAAAAC";
+
+    pub(crate) fn random_dna(len: usize, seed: u64) -> String {
+        let mut state = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (0..len).map(|_| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            b"ATCG"[(state >> 33) as usize % 4] as char
+        }).collect()
+    }
+
+    // flank + (repeat + spacer) * n_spacers + repeat + flank
+    pub(crate) fn synthetic_array(repeat: &str, n_spacers: usize, spacer_len: usize, flank_len: usize) -> String {
+        let mut contig = random_dna(flank_len, 1);
+        for i in 0..n_spacers {
+            contig.push_str(repeat);
+            contig.push_str(&random_dna(spacer_len, 100 + i as u64));
+        }
+        contig.push_str(repeat);
+        contig.push_str(&random_dna(flank_len, 2));
+        contig
+    }
+
+    #[test]
+    fn find_kmer_loc_clusters_finds_min_reps() {
+        let result = find_kmer_loc_clusters(&[10, 80, 150, 5000], &DetectionParams::default()).unwrap();
+        assert_eq!(result, vec![vec![10, 80, 150]]);
+    }
+
+    #[test]
+    fn collapse_shifted_candidates_groups_shifts() {
+        let k = Seq::from_dna("ATCG".to_string()).unwrap();
+        let candidates = vec![
+            KmerLocs::new(k.clone(), vec![11, 111, 211]),
+            KmerLocs::new(k.clone(), vec![10, 110, 210]),
+            KmerLocs::new(k.clone(), vec![12, 112, 212]),
+            KmerLocs::new(k.clone(), vec![500, 580, 660]),
+        ];
+        let result: Vec<Vec<usize>> = collapse_shifted_candidates(candidates, 70).iter()
+            .map(|c| c.locs())
+            .collect();
+        assert_eq!(result, vec![vec![10, 110, 210], vec![500, 580, 660]]);
+    }
+
+    #[test]
+    fn find_crisprs_rejects_tandem_copies() {
+        // identical "spacers" between the repeats
+        let spacer = random_dna(30, 9);
+        let contig = format!("{}{}",
+            random_dna(200, 1),
+            [TEST_REPEAT, &spacer, TEST_REPEAT, &spacer, TEST_REPEAT, &random_dna(200, 2)].concat()
+        );
+        let seq = Seq::from_dna(contig).unwrap();
+        let (arrays, rejected) = find_crisprs_with_rejections(&seq, "contig", &DetectionParams::default()).unwrap();
+        assert!(arrays.is_empty());
+        assert!(!rejected.is_empty());
+    }
+
+    #[test]
+    fn find_crisprs_finds_array() {
+        let seq = Seq::from_dna(synthetic_array(TEST_REPEAT, 4, 30, 300)).unwrap();
+        let result = find_crisprs(&seq, "contig", &DetectionParams::default()).unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].repeats().len(), 5);
+        assert_eq!(result[0].source_name(), "contig");
+        assert_eq!(result[0].location(), [300, 300 + 5 * 36 + 4 * 30]);
+        assert_eq!(result[0].repeats()[0].to_string(), TEST_REPEAT);
+    }
+
+    #[test]
+    fn array_id_uses_first_header_word() {
+        let seq = Seq::from_dna(synthetic_array(TEST_REPEAT, 4, 30, 300)).unwrap();
+        let result = find_crisprs(&seq, "plasmid circular=true", &DetectionParams::default()).unwrap();
+        assert_eq!(result[0].contig(), "plasmid");
+        assert_eq!(result[0].id(), "plasmid:300-600");
+        assert!(result[0].to_summary().starts_with("plasmid:300-600\tplasmid\t"));
+    }
+
+    #[test]
+    fn find_crisprs_rejects_invalid_params() {
+        let seq = Seq::from_dna(synthetic_array(TEST_REPEAT, 4, 30, 300)).unwrap();
+        let params = DetectionParams { min_reps: 0, ..Default::default() };
+        assert!(find_crisprs(&seq, "contig", &params).is_err());
+    }
+
+    #[test]
+    fn find_crisprs_accepts_view() {
+        let seq = Seq::from_dna(synthetic_array(TEST_REPEAT, 4, 30, 300)).unwrap();
+        let result = find_crisprs(seq.slice(200, seq.len()).unwrap(), "contig", &DetectionParams::default()).unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].location(), [100, 100 + 5 * 36 + 4 * 30]);
+    }
+
+    #[test]
+    fn find_crisprs_merges_kmer_sizes() {
+        let seq = Seq::from_dna(synthetic_array(TEST_REPEAT, 4, 30, 300)).unwrap();
+        let params = DetectionParams { kmer_sizes: vec![9, 11, 15], ..Default::default() };
+        let result = find_crisprs(&seq, "contig", &params).unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].repeats().len(), 5);
+        assert_eq!(result[0].supporting_k(), &[9, 11, 15]);
+    }
+
+    #[test]
+    fn find_crisprs_searches_reverse_strand() {
+        let seq = Seq::from_dna(synthetic_array(TEST_REPEAT, 4, 30, 300)).unwrap().rev_comp();
+        let result = find_crisprs(&seq, "contig", &DetectionParams::default()).unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].location(), [300, 300 + 5 * 36 + 4 * 30]);
+        let expected_strand = result[0].orientation().unwrap_or(Strand::Forward);
+        assert_eq!(result[0].strand(), expected_strand);
+        match result[0].strand() {
+            Strand::Forward => assert_eq!(result[0].consensus().rev_comp().to_string(), TEST_REPEAT),
+            Strand::Reverse => assert_eq!(result[0].consensus().to_string(), TEST_REPEAT),
+        }
+    }
+
+    #[test]
+    fn find_crisprs_flags_gap_in_array() {
+        let spacers: Vec<String> = (0..4).map(|i| random_dna(30, 10 + i)).collect();
+        let gapped = format!("{}{}{}", random_dna(5, 20), "N".repeat(100), random_dna(5, 21));
+        let contig = [
+            &random_dna(300, 1), TEST_REPEAT, &spacers[0], TEST_REPEAT, &spacers[1], TEST_REPEAT,
+            &gapped, TEST_REPEAT, &spacers[2], TEST_REPEAT, &spacers[3], TEST_REPEAT, &random_dna(300, 2)
+        ].concat();
+        let seq = Seq::from_dna(contig).unwrap();
+        let result = find_crisprs(&seq, "contig", &DetectionParams::default()).unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].repeats().len(), 6);
+        assert_eq!(result[0].flags(), &[ArrayFlag::GapWithin]);
+        assert!(result[0].possibly_incomplete());
+    }
+
+    #[test]
+    fn reverse_complement_flips_array() {
+        let seq = Seq::from_dna(synthetic_array(TEST_REPEAT, 2, 30, 50)).unwrap();
+        let array = CRISPRArray::from_repeat_starts(&seq, "contig", &[50, 116, 182], 36).unwrap();
+        let rc = array.reverse_complement();
+        assert_eq!(rc.strand(), Strand::Reverse);
+        assert_eq!(rc.location(), array.location());
+        assert_eq!(rc.repeats()[0].start(), 182);
+        assert_eq!(rc.spacers()[0], array.spacers()[1].rev_comp());
+        assert_eq!(rc.consensus(), &array.consensus().rev_comp());
+    }
+
     #[test]
     fn crispr_to_fasta_works() {
         let rep = Seq::from_dna("ATCG".to_string()).unwrap();
         let spacer = Seq::from_dna("AAAA".to_string()).unwrap();
         let cr = CRISPRArray {
-            repeats: vec![rep.clone(), rep.clone()],
+            repeats: vec![Repeat::exact(rep.clone(), 5), Repeat::exact(rep.clone(), 13)],
             spacers: vec![spacer.clone()],
             source_name: "test".to_string(),
-            location: [5usize, 10usize]
+            location: [5usize, 10usize],
+            consensus: rep.clone(),
+            conservation: ConservationProfile::default(),
+            strand: Strand::Forward,
+            orientation: None,
+            flags: Vec::new(),
+            confidence: 0.5,
+            evidence: EvidenceLevel::Two,
+            supporting_k: vec![9, 11],
+            library_match: None,
+            leader: None,
+            interruptions: Vec::new(),
+            boundary_shift: [0, 0],
+            merged_calls: Vec::new(),
         };
-        let expected = ">test\nATCGAAAAATCG".to_string();
+        let expected = ">test consensus=ATCG confidence=0.50 evidence_level=2 supporting_k=9,11 library_match=- interruptions=- flags=-\nATCGAAAAATCG".to_string();
         let result = cr.to_fasta("test");
         assert_eq!(result, expected);
     }
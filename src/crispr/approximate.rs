@@ -0,0 +1,254 @@
+use crate::align::{compatible, fit, iupac_mask};
+use crate::slice::SeqSlice;
+
+use super::DetectionParams;
+use super::extend::ExtendedRepeats;
+use super::repeat::Repeat;
+
+// Share of copies that must agree for a column to be added during tolerant extension
+const MIN_COLUMN_AGREEMENT: f32 = 0.6;
+// Gaps between copies longer than this many typical periods are searched for a missing copy
+const MISSING_COPY_PERIODS: f32 = 1.5;
+
+
+/// Turn exact repeat copies into approximate ones: extend through columns where
+/// most copies agree, realign every copy to the consensus allowing the maximum
+/// mismatches and indels in `params`, and look for degenerate copies in unusually
+/// long gaps. Copies that can't be aligned within those limits split the array,
+/// so this returns the runs of acceptable copies.
+pub fn approximate_repeats(
+    source_seq: SeqSlice,
+    source: &[u8],
+    ext: &ExtendedRepeats,
+    params: &DetectionParams,
+) -> Vec<Vec<Repeat>> {
+    let (starts, len) = tolerant_extension(source, ext, params);
+    let consensus = majority_consensus(source, &starts, len);
+
+    let mut runs = Vec::new();
+    let mut this_run: Vec<Repeat> = Vec::new();
+    for start in starts {
+        let win_start = start.saturating_sub(params.max_indels);
+        let win_end = (start + len + params.max_indels).min(source.len());
+        let aln = fit(&consensus, &source[win_start .. win_end]);
+        let overlaps_previous = this_run.last().is_some_and(|r| r.end() > win_start + aln.start);
+        if aln.mismatches() > params.max_mismatches || aln.indels() > params.max_indels || overlaps_previous {
+            if !this_run.is_empty() {
+                runs.push(std::mem::take(&mut this_run));
+            }
+            continue
+        }
+        let rep = Repeat::from_alignment(source_seq, &consensus, source, &aln, win_start)
+            .expect("Window is within the source sequence");
+        this_run.push(rep);
+    }
+    if !this_run.is_empty() {
+        runs.push(this_run);
+    }
+
+    runs.into_iter()
+        .map(|run| fill_missing_copies(source_seq, source, run, &consensus, params))
+        .filter(|run| run.len() >= 2)
+        .collect()
+}
+
+// Continue extending past the exact repeat boundaries through columns where most
+// copies agree, as long as no copy collects more than `max_mismatches` differences.
+// Ends are trimmed back so the repeat always starts and ends on a unanimous column.
+fn tolerant_extension(source: &[u8], ext: &ExtendedRepeats, params: &DetectionParams) -> (Vec<usize>, usize) {
+    let max_len = params.max_rep_size;
+    let mut starts = ext.starts.clone();
+    let mut len = ext.len;
+    let min_gap = starts.windows(2)
+        .map(|w| w[1] - w[0])
+        .min()
+        .unwrap_or(usize::MAX);
+    let mut mismatches = vec![0usize; starts.len()];
+
+    // (columns added, columns added since the last unanimous one)
+    let mut left = (0usize, 0usize);
+    let mut right = (0usize, 0usize);
+    let mut can_left = true;
+    let mut can_right = true;
+    while (can_left || can_right) && len < max_len && len + 1 < min_gap {
+        if can_left {
+            let col: Option<Vec<u8>> = starts.iter()
+                .map(|s| s.checked_sub(left.0 + 1).and_then(|p| source.get(p).copied()))
+                .collect();
+            can_left = take_column(col, &mut mismatches, params.max_mismatches, &mut left);
+            if can_left { len += 1 }
+        }
+        if can_right && len < max_len && len + 1 < min_gap {
+            let col: Option<Vec<u8>> = starts.iter()
+                .map(|s| source.get(s + ext.len + right.0).copied())
+                .collect();
+            can_right = take_column(col, &mut mismatches, params.max_mismatches, &mut right);
+            if can_right { len += 1 }
+        }
+    }
+
+    // Drop trailing columns that weren't unanimous
+    let left_kept = left.0 - left.1;
+    let right_kept = right.0 - right.1;
+    starts.iter_mut().for_each(|s| *s -= left_kept);
+    (starts, ext.len + left_kept + right_kept)
+}
+
+// Decide whether a column can be added, updating the per-copy mismatch counts
+fn take_column(col: Option<Vec<u8>>, mismatches: &mut [usize], max_mismatches: usize, progress: &mut (usize, usize)) -> bool {
+    let col = match col {
+        Some(c) => c,
+        None => return false,
+    };
+    let majority = majority_base(&col);
+    if majority == b'N' {
+        return false
+    }
+    let agreeing = col.iter().filter(|b| compatible(**b, majority)).count();
+    if (agreeing as f32) < MIN_COLUMN_AGREEMENT * col.len() as f32 {
+        return false
+    }
+    for (count, base) in mismatches.iter_mut().zip(col.iter()) {
+        if !compatible(*base, majority) {
+            *count += 1;
+        }
+    }
+    if mismatches.iter().any(|m| *m > max_mismatches) {
+        return false
+    }
+    progress.0 += 1;
+    progress.1 = match agreeing == col.len() {
+        true => 0,
+        false => progress.1 + 1,
+    };
+    true
+}
+
+fn majority_base(col: &[u8]) -> u8 {
+    let counts = base_counts(col.iter().copied());
+    let best = (0..4).max_by_key(|i| (counts[*i], 3 - i))
+        .expect("Range is not empty");
+    match counts[best] {
+        0 => b'N',
+        _ => b"ACGT"[best],
+    }
+}
+
+/// Copies in a column that could be each of A, C, G and T. An ambiguity code
+/// counts towards every base it stands for, and N towards none
+pub(crate) fn base_counts(col: impl Iterator<Item = u8>) -> [usize; 4] {
+    let mut counts = [0usize; 4];
+    for b in col {
+        let mask = iupac_mask(b);
+        for (i, count) in counts.iter_mut().enumerate() {
+            *count += usize::from(mask >> i & 1);
+        }
+    }
+    counts
+}
+
+/// Most common base at each position of the ungapped copies
+pub(crate) fn majority_consensus(source: &[u8], starts: &[usize], len: usize) -> Vec<u8> {
+    (0..len).map(|i| {
+        let col: Vec<u8> = starts.iter()
+            .filter_map(|s| source.get(s + i).copied())
+            .collect();
+        majority_base(&col)
+    }).collect()
+}
+
+// Degenerate copies with no conserved seed k-mer show up as a gap of about two
+// periods. Look for the consensus in those gaps.
+fn fill_missing_copies(source_seq: SeqSlice, source: &[u8], run: Vec<Repeat>, consensus: &[u8], params: &DetectionParams) -> Vec<Repeat> {
+    if run.len() < 2 {
+        return run
+    }
+    // Any missing copies make periods longer, so the shortest is the best estimate
+    let period = run.windows(2)
+        .map(|w| w[1].start() - w[0].start())
+        .min()
+        .expect("Checked there are at least two copies");
+    let max_gap = (period as f32 * MISSING_COPY_PERIODS) as usize;
+
+    let mut filled = Vec::with_capacity(run.len());
+    let mut iter = run.into_iter().peekable();
+    while let Some(rep) = iter.next() {
+        let prev_end = rep.end();
+        let prev_start = rep.start();
+        filled.push(rep);
+        let next_start = match iter.peek() {
+            Some(next) => next.start(),
+            None => break,
+        };
+        let mut last_start = prev_start;
+        let mut last_end = prev_end;
+        while next_start - last_start > max_gap {
+            let aln = fit(consensus, &source[last_end .. next_start]);
+            if aln.mismatches() > params.max_mismatches || aln.indels() > params.max_indels {
+                break
+            }
+            let found = Repeat::from_alignment(source_seq, consensus, source, &aln, last_end)
+                .expect("Gap is within the source sequence");
+            last_start = found.start();
+            last_end = found.end();
+            filled.push(found);
+        }
+    }
+    filled
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fasta::Seq;
+    use super::super::tests::{random_dna, TEST_REPEAT};
+
+    fn with_mismatch(repeat: &str, pos: usize) -> String {
+        let mut bytes = repeat.as_bytes().to_vec();
+        bytes[pos] = if bytes[pos] == b'A' { b'C' } else { b'A' };
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn approximate_repeats_tolerates_mismatch() {
+        let degenerate = with_mismatch(TEST_REPEAT, 30);
+        let contig = [
+            &random_dna(50, 1), TEST_REPEAT, &random_dna(30, 2), TEST_REPEAT, &random_dna(30, 3),
+            TEST_REPEAT, &random_dna(30, 4), &degenerate, &random_dna(50, 5)
+        ].concat();
+        let seq = Seq::from_dna(contig.clone()).unwrap();
+        // exact seed covering the start of each repeat
+        let ext = ExtendedRepeats { starts: vec![50, 116, 182, 248], len: 30 };
+        let runs = approximate_repeats(seq.as_slice(), contig.as_bytes(), &ext, &DetectionParams::default());
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].len(), 4);
+        assert!(runs[0].iter().all(|r| r.len() == TEST_REPEAT.len()));
+        assert_eq!(runs[0][3].variants().len(), 1);
+        assert!(runs[0][..3].iter().all(|r| r.is_exact()));
+    }
+
+    #[test]
+    fn approximate_repeats_finds_missing_copy() {
+        let degenerate = with_mismatch(&with_mismatch(TEST_REPEAT, 5), 20);
+        let contig = [
+            &random_dna(50, 1), TEST_REPEAT, &random_dna(30, 2), TEST_REPEAT, &random_dna(30, 3),
+            &degenerate, &random_dna(30, 4), TEST_REPEAT, &random_dna(50, 5)
+        ].concat();
+        let seq = Seq::from_dna(contig.clone()).unwrap();
+        let ext = ExtendedRepeats { starts: vec![50, 116, 248], len: 36 };
+        let runs = approximate_repeats(seq.as_slice(), contig.as_bytes(), &ext, &DetectionParams::default());
+        assert_eq!(runs.len(), 1);
+        let starts: Vec<usize> = runs[0].iter().map(|r| r.start()).collect();
+        assert_eq!(starts, vec![50, 116, 182, 248]);
+        assert_eq!(runs[0][2].variants().len(), 2);
+    }
+
+    #[test]
+    fn majority_base_counts_ambiguity_codes() {
+        assert_eq!(majority_base(b"AARG"), b'A');
+        assert_eq!(majority_base(b"CRRG"), b'G');
+        assert_eq!(majority_base(b"NNNC"), b'C');
+        assert_eq!(majority_base(b"NN"), b'N');
+    }
+}
@@ -0,0 +1,155 @@
+use crate::slice::SeqSlice;
+
+use super::{CRISPRArray, DetectionParams};
+use super::approximate::base_counts;
+use super::repeat::Repeat;
+
+// Furthest a repeat/spacer boundary can move, in bases
+const MAX_SHIFT: isize = 3;
+// Proportion of copies that must share a base for its column to count as repeat
+const MIN_COLUMN_AGREEMENT: f32 = 0.75;
+
+
+/// Move the start and end of every repeat by the same amount to make the
+/// repeats as conserved and the spacers as diverse as possible. Columns next to
+/// a boundary where most copies share a base belong in the repeat, and the rest
+/// in the spacers, so spacers no longer share a prefix or suffix when extension
+/// stopped short, and repeats lose spacer bases when it went too far.
+///
+/// Shifts that would take a repeat or spacer outside the length limits in
+/// `params` aren't considered. The array must be on the strand it was found on.
+pub fn optimise_boundaries(array: CRISPRArray, source_seq: SeqSlice, source: &[u8], params: &DetectionParams) -> CRISPRArray {
+    let start_agreement: Vec<f32> = (-MAX_SHIFT .. MAX_SHIFT)
+        .map(|j| column_agreement(source, array.repeats.iter().map(|r| r.start() as isize + j)))
+        .collect();
+    let end_agreement: Vec<f32> = (-MAX_SHIFT .. MAX_SHIFT)
+        .map(|j| column_agreement(source, array.repeats.iter().map(|r| r.end() as isize + j)))
+        .collect();
+
+    // Columns at or after the new start are repeat, as are columns before the new end
+    let start_score = |shift: isize| -> f32 {
+        start_agreement[(shift + MAX_SHIFT) as usize ..].iter().map(|a| a - MIN_COLUMN_AGREEMENT).sum()
+    };
+    let end_score = |shift: isize| -> f32 {
+        end_agreement[.. (shift + MAX_SHIFT) as usize].iter().map(|a| a - MIN_COLUMN_AGREEMENT).sum()
+    };
+
+    let mut best: Option<(f32, isize, isize)> = None;
+    for start_shift in -MAX_SHIFT ..= MAX_SHIFT {
+        for end_shift in -MAX_SHIFT ..= MAX_SHIFT {
+            if !fits_limits(&array, start_shift, end_shift, source.len(), params) {
+                continue
+            }
+            let score = start_score(start_shift) + end_score(end_shift);
+            let better = match best {
+                None => true,
+                // Prefer the smallest move when scores tie
+                Some((s, bs, be)) => score > s
+                    || (score == s && start_shift.abs() + end_shift.abs() < bs.abs() + be.abs()),
+            };
+            if better {
+                best = Some((score, start_shift, end_shift));
+            }
+        }
+    }
+    let (start_shift, end_shift) = match best {
+        Some((_, 0, 0)) | None => return array,
+        Some((_, s, e)) => (s, e),
+    };
+
+    let repeats = array.repeats.iter()
+        .map(|r| {
+            let start = (r.start() as isize + start_shift) as usize;
+            let end = (r.end() as isize + end_shift) as usize;
+            let mut rep = Repeat::exact(source_seq.slice(start, end)?.to_seq(), start);
+            if r.is_degenerate() {
+                rep.set_degenerate();
+            }
+            Ok(rep)
+        })
+        .collect::<anyhow::Result<Vec<Repeat>>>();
+    match repeats.and_then(|reps| CRISPRArray::from_repeats(source_seq, &array.source_name, reps)) {
+        Ok(mut shifted) => {
+            shifted.supporting_k = array.supporting_k.clone();
+            shifted.boundary_shift = [-start_shift, end_shift];
+            shifted
+        },
+        Err(_) => array,
+    }
+}
+
+fn fits_limits(array: &CRISPRArray, start_shift: isize, end_shift: isize, source_len: usize, params: &DetectionParams) -> bool {
+    let reps_fit = array.repeats.iter().all(|r| {
+        let start = r.start() as isize + start_shift;
+        let end = r.end() as isize + end_shift;
+        start >= 0 && end <= source_len as isize
+            && (params.min_rep_size ..= params.max_rep_size).contains(&((end - start).max(0) as usize))
+    });
+    let spacers_fit = array.repeats.windows(2).all(|pair| {
+        let len = (pair[1].start() as isize + start_shift) - (pair[0].end() as isize + end_shift);
+        (params.min_spacer_size as isize ..= params.max_spacer_size as isize).contains(&len)
+    });
+    reps_fit && spacers_fit
+}
+
+// Proportion of copies with the most common base at the given positions.
+// Positions outside the sequence and Ns count against agreement
+fn column_agreement(source: &[u8], positions: impl Iterator<Item = isize>) -> f32 {
+    let mut total = 0usize;
+    let counts = base_counts(positions
+        .inspect(|_| total += 1)
+        .filter_map(|pos| usize::try_from(pos).ok().and_then(|p| source.get(p)).copied())
+    );
+    match total {
+        0 => 0.0,
+        _ => *counts.iter().max().expect("counts is not empty") as f32 / total as f32,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fasta::Seq;
+    use super::super::tests::{synthetic_array, TEST_REPEAT};
+
+    fn array_with_boundaries(start_offset: isize, len: usize) -> (Seq, CRISPRArray) {
+        let seq = Seq::from_dna(synthetic_array(TEST_REPEAT, 4, 30, 100)).unwrap();
+        let starts: Vec<usize> = (0..5).map(|i| (100 + 66 * i as isize + start_offset) as usize).collect();
+        let array = CRISPRArray::from_repeat_starts(&seq, "contig", &starts, len).unwrap();
+        (seq, array)
+    }
+
+    #[test]
+    fn grows_short_repeats() {
+        let (seq, array) = array_with_boundaries(2, 33);
+        let result = optimise_boundaries(array, seq.as_slice(), &seq.to_bytes(), &DetectionParams::default());
+        assert_eq!(result.boundary_shift(), [2, 1]);
+        assert!(result.repeats().iter().all(|r| r.to_string() == TEST_REPEAT));
+    }
+
+    #[test]
+    fn trims_long_repeats() {
+        let (seq, array) = array_with_boundaries(-2, 39);
+        let result = optimise_boundaries(array, seq.as_slice(), &seq.to_bytes(), &DetectionParams::default());
+        assert_eq!(result.boundary_shift(), [-2, -1]);
+        assert_eq!(result.consensus().to_string(), TEST_REPEAT);
+    }
+
+    #[test]
+    fn keeps_spacers_within_limits() {
+        let (seq, array) = array_with_boundaries(-2, 39);
+        let params = DetectionParams { max_spacer_size: 28, ..Default::default() };
+        let result = optimise_boundaries(array, seq.as_slice(), &seq.to_bytes(), &params);
+        assert_eq!(result.boundary_shift(), [0, -1]);
+        assert!(result.spacers().iter().all(|s| s.len() <= 28));
+    }
+
+    #[test]
+    fn leaves_correct_boundaries() {
+        let (seq, array) = array_with_boundaries(0, 36);
+        let result = optimise_boundaries(array, seq.as_slice(), &seq.to_bytes(), &DetectionParams::default());
+        assert_eq!(result.boundary_shift(), [0, 0]);
+        assert_eq!(result.location(), [100, 100 + 66 * 4 + 36]);
+    }
+}
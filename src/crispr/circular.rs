@@ -0,0 +1,125 @@
+use anyhow::Result;
+
+use crate::slice::SeqSlice;
+
+use super::{CRISPRArray, DetectionParams, Rejection, find_on_both_strands};
+
+// Bases either side of the origin of a circular sequence that are joined to
+// find arrays spanning it
+const WRAP_LENGTH: usize = 20_000;
+
+
+/// As `find_crisprs_with_rejections`, for a circular sequence. Arrays are
+/// found in the sequence as it is, and arrays spanning the origin in a window
+/// of the sequence with its end joined to its start. None are flagged as
+/// truncated. Coordinates are reported modulo the sequence length, so an
+/// array spanning the origin has an end before its start.
+pub fn find_crisprs_circular_with_rejections<'a>(source: impl Into<SeqSlice<'a>>, source_name: &str, params: &DetectionParams) -> Result<(Vec<CRISPRArray>, Vec<Rejection>)> {
+    params.validate()?;
+    let source_seq = source.into();
+    let len = source_seq.len();
+    if len < 2 {
+        return Ok((Vec::new(), Vec::new()))
+    }
+    let (arrays, mut rejected) = find_on_both_strands(source_seq, source_name, params, Some(len));
+
+    // Joining all of a short sequence to itself would let the copies of an
+    // array chain into one call going round the sequence twice
+    let wrap = (len - 1).min(WRAP_LENGTH);
+    let offset = len - wrap;
+    let window = source_seq.slice(offset, len)?.to_seq().concat(&source_seq.slice(0, wrap)?.to_seq());
+    let (window_arrays, window_rejected) = find_on_both_strands(window.as_slice(), source_name, params, Some(len));
+    // Every other call was already made on the sequence as it is. The origin
+    // is `wrap` bases into the window, and a call longer than the sequence
+    // covers some of it twice
+    let spans_origin = |a: &CRISPRArray| {
+        let [start, end] = a.location;
+        start < wrap && end > wrap && end - start <= len
+    };
+    let spanning: Vec<CRISPRArray> = window_arrays.into_iter()
+        .filter(|a| spans_origin(a))
+        .collect();
+
+    // Parts of an array spanning the origin are also found on their own at either end
+    let overlaps_spanning = |[start, end]: [usize; 2]| spanning.iter()
+        .any(|s| start < s.location[1] - wrap || end > s.location[0] + offset);
+    let mut arrays: Vec<CRISPRArray> = arrays.into_iter()
+        .filter(|a| !overlaps_spanning(a.location))
+        .collect();
+    arrays.extend(spanning.into_iter().map(|a| a.into_circular_coords(offset, len)));
+
+    rejected.extend(window_rejected.into_iter()
+        .filter(|r| spans_origin(r.array()))
+        .map(|r| {
+            let (array, reason) = r.into_parts();
+            Rejection::new(array.into_circular_coords(offset, len), reason)
+        }));
+    Ok((arrays, rejected))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fasta::Seq;
+    use super::super::tests::{synthetic_array, TEST_REPEAT};
+
+    #[test]
+    fn finds_array_spanning_origin() {
+        let linear = synthetic_array(TEST_REPEAT, 4, 30, 300);
+        // Put the origin in the middle of the third repeat
+        let cut = 300 + 2 * 66 + 10;
+        let circular = format!("{}{}", &linear[cut..], &linear[..cut]);
+        let seq = Seq::from_dna(circular).unwrap();
+        let (arrays, _) = find_crisprs_circular_with_rejections(&seq, "plasmid", &DetectionParams::default()).unwrap();
+        assert_eq!(arrays.len(), 1);
+        assert_eq!(arrays[0].repeats().len(), 5);
+        let array_start = seq.len() - cut + 300;
+        assert_eq!(arrays[0].location(), [array_start, array_start + 5 * 36 + 4 * 30 - seq.len()]);
+        assert!(arrays[0].repeats().iter().all(|r| r.start() < seq.len()));
+    }
+
+    #[test]
+    fn finds_array_spanning_origin_of_long_sequence() {
+        // Long enough that only a window around the origin is searched again
+        let linear = synthetic_array(TEST_REPEAT, 4, 30, WRAP_LENGTH + 5000);
+        let cut = WRAP_LENGTH + 5000 + 2 * 66 + 10;
+        let circular = format!("{}{}", &linear[cut..], &linear[..cut]);
+        let seq = Seq::from_dna(circular).unwrap();
+        let (arrays, _) = find_crisprs_circular_with_rejections(&seq, "plasmid", &DetectionParams::default()).unwrap();
+        assert_eq!(arrays.len(), 1);
+        let array_start = seq.len() - cut + WRAP_LENGTH + 5000;
+        assert_eq!(arrays[0].location(), [array_start, array_start + 5 * 36 + 4 * 30 - seq.len()]);
+        let starts: Vec<usize> = arrays[0].repeats().iter().map(|r| r.start()).collect();
+        assert_eq!(starts, [array_start, array_start + 66, array_start + 2 * 66, 66 - 10, 2 * 66 - 10]);
+    }
+
+    #[test]
+    fn keeps_fragments_either_side_of_origin_apart() {
+        // The linear pass sees three repeats at the end and four at the start,
+        // an "insertion" apart across the rest of the plasmid
+        let linear = synthetic_array(TEST_REPEAT, 6, 30, 3000);
+        let cut = 3000 + 2 * 66 + 36;
+        let circular = format!("{}{}", &linear[cut..], &linear[..cut]);
+        let seq = Seq::from_dna(circular).unwrap();
+        let (arrays, _) = find_crisprs_circular_with_rejections(&seq, "plasmid", &DetectionParams::default()).unwrap();
+        assert_eq!(arrays.len(), 1);
+        assert_eq!(arrays[0].repeats().len(), 7);
+        assert!(arrays[0].interruptions().is_empty());
+        assert!(arrays[0].repeats().iter().all(|r| !r.is_degenerate()));
+        let array_start = seq.len() - cut + 3000;
+        assert_eq!(arrays[0].location(), [array_start, array_start + 7 * 36 + 6 * 30 - seq.len()]);
+    }
+
+    #[test]
+    fn finds_array_in_short_sequence() {
+        // Shorter than the k-mer clustering window twice over
+        let seq = Seq::from_dna(synthetic_array(TEST_REPEAT, 4, 30, 100)).unwrap();
+        assert_eq!(seq.len(), 500);
+        let (arrays, rejected) = find_crisprs_circular_with_rejections(&seq, "plasmid", &DetectionParams::default()).unwrap();
+        assert_eq!(arrays.len(), 1);
+        assert_eq!(arrays[0].repeats().len(), 5);
+        assert_eq!(arrays[0].location(), [100, 400]);
+        assert!(rejected.iter().all(|r| r.array().repeats().len() <= 5));
+    }
+}
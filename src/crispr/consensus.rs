@@ -0,0 +1,191 @@
+use crate::align::{fit, AlignOp};
+use crate::fasta::base_code;
+
+use super::repeat::{Repeat, Variant, variants_from_ops};
+
+// Order of the counts in a column. Gaps are deletions relative to the consensus
+const COLUMN_SYMBOLS: &[u8; 5] = b"ACGT-";
+
+
+/// Base composition of one consensus position across all repeat copies
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnConservation {
+    counts: [usize; 5],
+    majority: u8,
+    entropy: f64,
+}
+
+impl ColumnConservation {
+    fn from_counts(counts: [usize; 5]) -> Self {
+        let total: usize = counts.iter().sum();
+        let best = (0..5).max_by_key(|i| (counts[*i], 4 - i))
+            .expect("Range is not empty");
+        let entropy = counts.iter()
+            .filter(|c| **c > 0)
+            .map(|c| {
+                let p = *c as f64 / total as f64;
+                -p * p.log2()
+            })
+            .sum::<f64>();
+        Self {
+            counts,
+            majority: COLUMN_SYMBOLS[best],
+            entropy: entropy.max(0.0),
+        }
+    }
+
+    /// Counts of A, C, G, T and gaps (or N), in that order
+    pub fn counts(&self) -> [usize; 5] {
+        self.counts
+    }
+
+    /// Most common symbol in the column. `-` if most copies lack this position
+    pub fn majority(&self) -> u8 {
+        self.majority
+    }
+
+    /// Shannon entropy (bits) of the column. 0 for a fully conserved column
+    pub fn entropy(&self) -> f64 {
+        self.entropy
+    }
+
+    /// Proportion of copies with the majority symbol
+    pub fn identity(&self) -> f64 {
+        let total: usize = self.counts.iter().sum();
+        match total {
+            0 => 0.0,
+            _ => *self.counts.iter().max().expect("Counts are not empty") as f64 / total as f64,
+        }
+    }
+}
+
+/// Per-position conservation of the repeats in an array, in consensus coordinates
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ConservationProfile {
+    columns: Vec<ColumnConservation>,
+}
+
+impl ConservationProfile {
+    pub fn columns(&self) -> &[ColumnConservation] {
+        &self.columns
+    }
+
+    /// Mean proportion of copies matching the consensus across all positions
+    pub fn mean_identity(&self) -> f64 {
+        if self.columns.is_empty() { return 0.0 }
+        self.columns.iter().map(|c| c.identity()).sum::<f64>() / self.columns.len() as f64
+    }
+}
+
+/// Consensus repeat of an array, its conservation profile, and the variants of
+/// each copy relative to that consensus
+pub(crate) struct Consensus {
+    pub seq: Vec<u8>,
+    pub profile: ConservationProfile,
+    pub variants: Vec<Vec<Variant>>,
+}
+
+/// Build the consensus of a set of repeat copies. Copies are aligned to a
+/// provisional consensus from the most common repeat length, then the
+/// majority symbol of each aligned column gives the final consensus.
+pub(crate) fn build(repeats: &[Repeat]) -> Consensus {
+    let copies: Vec<Vec<u8>> = repeats.iter().map(|r| r.seq().to_bytes()).collect();
+    let mut seq = provisional_consensus(&copies);
+    // A second pass settles columns that the first alignment placed differently
+    for _ in 0..2 {
+        let columns = column_counts(&seq, &copies);
+        let next: Vec<u8> = columns.iter()
+            .map(|c| ColumnConservation::from_counts(*c).majority)
+            .filter(|b| *b != b'-')
+            .collect();
+        if next == seq || next.is_empty() { break }
+        seq = next;
+    }
+
+    let profile = ConservationProfile {
+        columns: column_counts(&seq, &copies).into_iter()
+            .map(ColumnConservation::from_counts)
+            .collect(),
+    };
+    let variants = copies.iter()
+        .map(|c| {
+            let aln = fit(&seq, c);
+            variants_from_ops(&seq, &c[aln.start ..], &aln.ops)
+        })
+        .collect();
+    Consensus { seq, profile, variants }
+}
+
+// Ungapped majority of the copies with the most common length
+fn provisional_consensus(copies: &[Vec<u8>]) -> Vec<u8> {
+    let mut lens: Vec<usize> = copies.iter().map(|c| c.len()).collect();
+    lens.sort();
+    let modal_len = lens.iter()
+        .max_by_key(|l| (lens.iter().filter(|x| x == l).count(), std::cmp::Reverse(**l)))
+        .copied()
+        .unwrap_or(0);
+    let modal: Vec<&Vec<u8>> = copies.iter().filter(|c| c.len() == modal_len).collect();
+    (0..modal_len).map(|i| {
+        let mut counts = [0usize; 5];
+        for c in &modal {
+            counts[base_code(c[i]).map_or(4, usize::from)] += 1;
+        }
+        ColumnConservation::from_counts(counts).majority
+    }).collect()
+}
+
+// Count the symbols aligned to each consensus position. Insertions in a copy are ignored
+fn column_counts(consensus: &[u8], copies: &[Vec<u8>]) -> Vec<[usize; 5]> {
+    let mut columns = vec![[0usize; 5]; consensus.len()];
+    for copy in copies {
+        let aln = fit(consensus, copy);
+        let mut c = 0usize;
+        let mut r = aln.start;
+        for op in aln.ops {
+            match op {
+                AlignOp::Match | AlignOp::Mismatch => {
+                    columns[c][base_code(copy[r]).map_or(4, usize::from)] += 1;
+                    c += 1;
+                    r += 1;
+                },
+                AlignOp::Insertion => r += 1,
+                AlignOp::Deletion => {
+                    columns[c][4] += 1;
+                    c += 1;
+                },
+            }
+        }
+    }
+    columns
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fasta::Seq;
+
+    fn rep(s: &str) -> Repeat {
+        Repeat::exact(Seq::from_dna(s.to_string()).unwrap(), 0)
+    }
+
+    #[test]
+    fn consensus_is_majority() {
+        let repeats = vec![rep("GATTACA"), rep("GATTACA"), rep("GATCACA"), rep("GATTAC")];
+        let result = build(&repeats);
+        assert_eq!(result.seq, b"GATTACA".to_vec());
+        assert_eq!(result.variants[2].len(), 1);
+        assert_eq!(result.variants[3], vec![Variant::Deletion { pos: 6, len: 1 }]);
+    }
+
+    #[test]
+    fn conservation_profile_columns() {
+        let repeats = vec![rep("GATTACA"), rep("GATTACA"), rep("GATCACA"), rep("GATCACA")];
+        let result = build(&repeats);
+        let col = &result.profile.columns()[3];
+        assert_eq!(col.counts(), [0, 2, 0, 2, 0]);
+        assert!((col.entropy() - 1.0).abs() < 1e-9);
+        assert_eq!(result.profile.columns()[0].entropy(), 0.0);
+        assert_eq!(result.profile.columns()[0].majority(), b'G');
+    }
+}
@@ -0,0 +1,151 @@
+use std::fmt;
+
+use crate::align::identity;
+use crate::fasta::Seq;
+
+use super::CRISPRArray;
+use super::validate::{length_deviation, max_spacer_spacer_identity};
+
+// Well characterised repeats. Similarity to these supports a call
+const KNOWN_REPEATS: [(&str, &str); 5] = [
+    ("Escherichia coli K-12 (I-E)", "CGGTTTATCCCCGCTGGCGCGGGGAACTC"),
+    ("Pseudomonas aeruginosa PA14 (I-F)", "GTTCACTGCCGTACAGGCAGCTTAGAAA"),
+    ("Streptococcus pyogenes SF370 (II-A)", "GTTTTAGAGCTATGCTGTTTTGAATGGTCCCAAAAC"),
+    ("Streptococcus thermophilus CRISPR1 (II-A)", "GTTTTTGTACTCTCAAGATTTAAGTAACTGTACAAC"),
+    ("Francisella novicida (V-A)", "GTCTAAGAACTTTAAATAATTTCTACTGTTGTAGAT"),
+];
+
+// Weights of each line of evidence in the confidence score. They sum to 1
+const REPEAT_COUNT_WEIGHT: f64 = 0.25;
+const CONSERVATION_WEIGHT: f64 = 0.2;
+const SPACER_REGULARITY_WEIGHT: f64 = 0.2;
+const SPACER_UNIQUENESS_WEIGHT: f64 = 0.15;
+const KNOWN_REPEAT_WEIGHT: f64 = 0.2;
+
+// Number of repeats at which the repeat count evidence is maxed out
+const CONFIDENT_REPEAT_COUNT: usize = 10;
+// Identity between unrelated sequences of CRISPR spacer length
+const BACKGROUND_IDENTITY: f64 = 0.45;
+
+
+/// Discrete summary of the confidence score, from 1 (weak) to 4 (strong)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EvidenceLevel {
+    One,
+    Two,
+    Three,
+    Four,
+}
+
+impl EvidenceLevel {
+    pub fn from_score(score: f64) -> Self {
+        match score {
+            s if s >= 0.75 => Self::Four,
+            s if s >= 0.55 => Self::Three,
+            s if s >= 0.35 => Self::Two,
+            _ => Self::One,
+        }
+    }
+}
+
+impl fmt::Display for EvidenceLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let level = match self {
+            Self::One => 1,
+            Self::Two => 2,
+            Self::Three => 3,
+            Self::Four => 4,
+        };
+        write!(f, "{level}")
+    }
+}
+
+/// Confidence in an array call between 0 and 1. A weighted sum of:
+/// - the number of repeats
+/// - mean conservation of the repeats
+/// - how regular the spacer lengths are
+/// - how different the spacers are from each other
+/// - similarity of the consensus repeat to well characterised CRISPR repeats
+///
+/// Evidence that can't be measured scores 0: conservation needs two repeats,
+/// and spacer regularity and uniqueness need two spacers to compare.
+pub fn confidence(array: &CRISPRArray) -> f64 {
+    let repeat_count = (array.repeats.len().min(CONFIDENT_REPEAT_COUNT) as f64 - 1.0)
+        / (CONFIDENT_REPEAT_COUNT as f64 - 1.0);
+
+    let conservation = match array.repeats.len() {
+        0 | 1 => 0.0,
+        _ => array.conservation.mean_identity(),
+    };
+
+    // Spacers holding assembly gaps or insertions say nothing about the array
+    let spacers: Vec<&Seq> = array.spacers.iter().enumerate()
+        .filter(|(i, s)| !s.contains_n() && !array.interruptions.iter().any(|int| int.spacer() == *i))
+        .map(|(_, s)| s)
+        .collect();
+    let (regularity, uniqueness) = match spacers.len() {
+        0 | 1 => (0.0, 0.0),
+        _ => {
+            let spacer_lens: Vec<usize> = spacers.iter().map(|s| s.len()).collect();
+            let regularity = (1.0 - 5.0 * length_deviation(&spacer_lens) as f64).max(0.0);
+            let spacer_bytes: Vec<Vec<u8>> = spacers.iter().map(|s| s.to_bytes()).collect();
+            let max_id = max_spacer_spacer_identity(&spacer_bytes) as f64;
+            (regularity, ((1.0 - max_id) / (1.0 - BACKGROUND_IDENTITY)).clamp(0.0, 1.0))
+        },
+    };
+
+    let known = ((known_repeat_identity(&array.consensus) - 0.5) / 0.5).clamp(0.0, 1.0);
+
+    REPEAT_COUNT_WEIGHT * repeat_count
+        + CONSERVATION_WEIGHT * conservation
+        + SPACER_REGULARITY_WEIGHT * regularity
+        + SPACER_UNIQUENESS_WEIGHT * uniqueness
+        + KNOWN_REPEAT_WEIGHT * known
+}
+
+/// Highest identity of a repeat, in either orientation, to a known CRISPR repeat
+pub fn known_repeat_identity(repeat: &Seq) -> f64 {
+    let fwd = repeat.to_bytes();
+    let rev = repeat.rev_comp().to_bytes();
+    KNOWN_REPEATS.iter()
+        .map(|(_, known)| identity(&fwd, known.as_bytes()).max(identity(&rev, known.as_bytes())) as f64)
+        .fold(0.0, f64::max)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::Repeat;
+    use super::super::tests::{random_dna, synthetic_array, TEST_REPEAT};
+
+    #[test]
+    fn evidence_level_from_score() {
+        assert_eq!(EvidenceLevel::from_score(0.9), EvidenceLevel::Four);
+        assert_eq!(EvidenceLevel::from_score(0.6), EvidenceLevel::Three);
+        assert_eq!(EvidenceLevel::from_score(0.1), EvidenceLevel::One);
+    }
+
+    #[test]
+    fn known_repeat_scores_higher() {
+        let known = Seq::from_dna(synthetic_array(TEST_REPEAT, 4, 30, 0)).unwrap();
+        let known = CRISPRArray::from_repeat_starts(&known, "known", &[0, 66, 132, 198, 264], 36).unwrap();
+        let novel_repeat = random_dna(36, 3);
+        let novel = Seq::from_dna(synthetic_array(&novel_repeat, 4, 30, 0)).unwrap();
+        let novel = CRISPRArray::from_repeat_starts(&novel, "novel", &[0, 66, 132, 198, 264], 36).unwrap();
+        assert!(confidence(&known) > confidence(&novel));
+        assert!(known_repeat_identity(known.consensus()) == 1.0);
+        assert!(known_repeat_identity(&known.consensus().rev_comp()) == 1.0);
+    }
+
+    #[test]
+    fn orphan_scores_below_array() {
+        let novel_repeat = random_dna(36, 3);
+        let novel = Seq::from_dna(synthetic_array(&novel_repeat, 4, 30, 0)).unwrap();
+        let novel = CRISPRArray::from_repeat_starts(&novel, "novel", &[0, 66, 132, 198, 264], 36).unwrap();
+        let known = Seq::from_dna(TEST_REPEAT.to_string()).unwrap();
+        let orphan = CRISPRArray::from_single_repeat("orphan", Repeat::exact(known, 0)).unwrap();
+        assert!(confidence(&orphan) < confidence(&novel));
+        assert_eq!(EvidenceLevel::from_score(confidence(&orphan)), EvidenceLevel::One);
+    }
+}
@@ -0,0 +1,104 @@
+use crate::align::iupac_mask;
+use crate::kmer::KmerLocs;
+
+
+/// Repeat copies grown out from a seed k-mer cluster.
+/// `starts` are the 0-based starts of each copy and every copy is `len` bases long.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtendedRepeats {
+    pub starts: Vec<usize>,
+    pub len: usize,
+}
+
+/// Grow the k-mer copies in `seed` left and right, one base at a time, for as
+/// long as every copy agrees on the next base. Extension stops when the copies
+/// disagree, a copy runs off the end of the sequence, neighbouring copies would
+/// touch, or the repeat reaches `max_len`. Returns `None` if the result is
+/// shorter than `min_len`.
+pub fn extend_seed(source: &[u8], seed: &KmerLocs, min_len: usize, max_len: usize) -> Option<ExtendedRepeats> {
+    let mut starts = seed.locs();
+    let mut len = seed.k().len();
+    if starts.len() < 2 {
+        return None
+    }
+    // Repeats may not grow into the next copy
+    let min_gap = starts.windows(2)
+        .map(|w| w[1] - w[0])
+        .min()
+        .expect("Checked there are at least two starts");
+
+    let mut can_left = true;
+    let mut can_right = true;
+    while (can_left || can_right) && len < max_len {
+        if can_left {
+            can_left = len + 1 < min_gap && column_agrees(source, &starts, |s| s.checked_sub(1));
+            if can_left {
+                starts.iter_mut().for_each(|s| *s -= 1);
+                len += 1;
+            }
+        }
+        if can_right && len < max_len {
+            can_right = len + 1 < min_gap && column_agrees(source, &starts, |s| Some(s + len));
+            if can_right {
+                len += 1;
+            }
+        }
+    }
+
+    match len >= min_len {
+        true => Some(ExtendedRepeats{starts, len}),
+        false => None,
+    }
+}
+
+// Check whether every copy can have the same base at the position given by
+// `pos(start)`. Ambiguity codes agree with the bases they stand for, N with nothing
+fn column_agrees<F: Fn(usize) -> Option<usize>>(source: &[u8], starts: &[usize], pos: F) -> bool {
+    starts.iter()
+        .try_fold(0b1111, |shared, s| Some(shared & iupac_mask(*source.get(pos(*s)?)?)))
+        .is_some_and(|shared| shared != 0)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fasta::Seq;
+
+    #[test]
+    fn extend_seed_stops_at_disagreement() {
+        //                    0         1         2         3
+        //                    0123456789012345678901234567890123456
+        let source = b"AAAATCGGTTTCCCCATCGGTTTGGGGATCGGTTTCA".to_vec();
+        let seed = KmerLocs::new(Seq::from_dna("TCGG".to_string()).unwrap(), vec![4, 16, 28]);
+        let result = extend_seed(&source, &seed, 4, 20).unwrap();
+        assert_eq!(result, ExtendedRepeats{starts: vec![3, 15, 27], len: 8});
+    }
+
+    #[test]
+    fn extend_seed_extends_through_ambiguity_codes() {
+        // R (A or G) in the first copy agrees with A in the others; N never agrees
+        let source = b"AAARTCGGTTTCCCCATCGGTTTGGGGATCGGTTTCA".to_vec();
+        let seed = KmerLocs::new(Seq::from_dna("TCGG".to_string()).unwrap(), vec![4, 16, 28]);
+        let result = extend_seed(&source, &seed, 4, 20).unwrap();
+        assert_eq!(result, ExtendedRepeats{starts: vec![3, 15, 27], len: 8});
+        let source = b"AAANTCGGTTTCCCCATCGGTTTGGGGATCGGTTTCA".to_vec();
+        let result = extend_seed(&source, &seed, 4, 20).unwrap();
+        assert_eq!(result, ExtendedRepeats{starts: vec![4, 16, 28], len: 7});
+    }
+
+    #[test]
+    fn extend_seed_respects_max_len() {
+        let source = b"ATCGATCGAAAAATCGATCGTTTTTATCGATCG".to_vec();
+        let seed = KmerLocs::new(Seq::from_dna("GATC".to_string()).unwrap(), vec![3, 15, 28]);
+        let result = extend_seed(&source, &seed, 4, 6).unwrap();
+        assert_eq!(result.len, 6);
+    }
+
+    #[test]
+    fn extend_seed_too_short_is_none() {
+        let source = b"CTCGGTTTCCCCATCGGTTTGGGGGTCGGTTTCA".to_vec();
+        let seed = KmerLocs::new(Seq::from_dna("TCGG".to_string()).unwrap(), vec![1, 13, 25]);
+        assert!(extend_seed(&source, &seed, 20, 70).is_none());
+    }
+}
@@ -0,0 +1,144 @@
+use crate::align::identity;
+use crate::fasta::{base_code, Seq};
+
+use super::{CRISPRArray, RejectReason};
+
+// Mean of spacer-spacer and spacer-repeat identity above which an array looks
+// like a tandem repeat. Unrelated sequences score about 0.45
+const MAX_TANDEM_SCORE: f32 = 0.62;
+// Normalised dinucleotide entropy below which the array is low complexity
+const MIN_COMPLEXITY: f32 = 0.55;
+// A consensus that matches itself this well at a short shift is a microsatellite
+const MIN_PERIODIC_IDENTITY: f32 = 0.85;
+const MAX_MICROSATELLITE_UNIT: usize = 6;
+
+
+/// Discard arrays that look like tandem repeats or low complexity sequence
+/// rather than CRISPRs. In those the "spacers" are similar to each other and
+/// to the repeat, or the whole region is made of a short repeated unit.
+pub fn filter(array: &CRISPRArray) -> Result<(), RejectReason> {
+    let consensus = array.consensus.to_bytes();
+    if let Some(unit) = short_period(&consensus) {
+        return Err(RejectReason::Microsatellite(unit))
+    }
+
+    let complexity = dinucleotide_complexity(array);
+    if complexity < MIN_COMPLEXITY {
+        return Err(RejectReason::LowComplexity(complexity))
+    }
+
+    let score = tandem_score(array);
+    if score > MAX_TANDEM_SCORE {
+        return Err(RejectReason::TandemRepeat(score))
+    }
+    Ok(())
+}
+
+/// Mean of the average pairwise spacer identity and the average spacer
+/// identity to the consensus repeat
+pub(crate) fn tandem_score(array: &CRISPRArray) -> f32 {
+    let consensus = array.consensus.to_bytes();
+    let spacers: Vec<Vec<u8>> = array.spacers.iter()
+        .filter(|s| !s.contains_n())
+        .map(Seq::to_bytes)
+        .collect();
+    if spacers.is_empty() {
+        return 0.0
+    }
+
+    let to_repeat = spacers.iter()
+        .map(|s| identity(s, &consensus))
+        .sum::<f32>() / spacers.len() as f32;
+
+    let mut pairs = 0usize;
+    let mut pair_total = 0.0f32;
+    for (i, a) in spacers.iter().enumerate() {
+        for b in &spacers[i + 1 ..] {
+            pair_total += identity(a, b);
+            pairs += 1;
+        }
+    }
+    match pairs {
+        0 => to_repeat,
+        _ => (to_repeat + pair_total / pairs as f32) / 2.0,
+    }
+}
+
+// Shortest unit (up to MAX_MICROSATELLITE_UNIT) the sequence is a repeat of
+fn short_period(seq: &[u8]) -> Option<usize> {
+    (1 ..= MAX_MICROSATELLITE_UNIT)
+        .filter(|p| *p < seq.len())
+        .find(|p| {
+            let matching = seq.iter().zip(&seq[*p ..])
+                .filter(|(a, b)| a == b)
+                .count();
+            matching as f32 / (seq.len() - p) as f32 >= MIN_PERIODIC_IDENTITY
+        })
+}
+
+// Shannon entropy of the dinucleotides across the array, scaled so 1 is the
+// maximum possible for 16 dinucleotides
+fn dinucleotide_complexity(array: &CRISPRArray) -> f32 {
+    let mut counts = [0usize; 16];
+    let mut total = 0usize;
+    let seqs = array.repeats.iter().map(|r| r.seq())
+        .chain(array.spacers.iter());
+    for seq in seqs {
+        for pair in seq.to_bytes().windows(2) {
+            if let (Some(a), Some(b)) = (base_code(pair[0]), base_code(pair[1])) {
+                counts[4 * a as usize + b as usize] += 1;
+                total += 1;
+            }
+        }
+    }
+    if total == 0 {
+        return 0.0
+    }
+    let entropy: f32 = counts.iter()
+        .filter(|c| **c > 0)
+        .map(|c| {
+            let p = *c as f32 / total as f32;
+            -p * p.log2()
+        })
+        .sum();
+    entropy / 4.0
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::{random_dna, synthetic_array, TEST_REPEAT};
+
+    #[test]
+    fn filter_passes_crispr() {
+        let contig = synthetic_array(TEST_REPEAT, 4, 30, 0);
+        let seq = Seq::from_dna(contig).unwrap();
+        let array = CRISPRArray::from_repeat_starts(&seq, "contig", &[0, 66, 132, 198, 264], 36).unwrap();
+        assert_eq!(filter(&array), Ok(()));
+    }
+
+    #[test]
+    fn filter_rejects_microsatellite() {
+        let contig = "AC".repeat(200);
+        let seq = Seq::from_dna(contig).unwrap();
+        let array = CRISPRArray::from_repeat_starts(&seq, "contig", &[0, 60, 120], 30).unwrap();
+        assert_eq!(filter(&array), Err(RejectReason::Microsatellite(2)));
+    }
+
+    #[test]
+    fn filter_rejects_tandem_repeat() {
+        // A 66 bp unit repeated with a few differences between copies
+        let unit = random_dna(66, 7);
+        let mut contig = String::new();
+        for i in 0..4 {
+            let mut copy = unit.clone().into_bytes();
+            copy[40 + i * 5] = b'A';
+            copy[45 + i * 3] = b'C';
+            contig.push_str(&String::from_utf8(copy).unwrap());
+        }
+        let seq = Seq::from_dna(contig).unwrap();
+        let array = CRISPRArray::from_repeat_starts(&seq, "contig", &[0, 66, 132, 198], 36).unwrap();
+        assert!(matches!(filter(&array), Err(RejectReason::TandemRepeat(_))));
+    }
+}
@@ -0,0 +1,150 @@
+use std::fmt;
+
+use crate::fasta::Seq;
+
+use super::CRISPRArray;
+
+
+/// The lower (`Start`) or higher (`End`) coordinate end of an array on the forward strand
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Side {
+    Start,
+    End,
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Start => f.write_str("start"),
+            Self::End => f.write_str("end"),
+        }
+    }
+}
+
+/// Notes on an array that don't stop it being called but affect how far it can be trusted
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ArrayFlag {
+    /// A spacer contains Ns, so the array continues across an assembly gap
+    GapWithin,
+    /// There is an assembly gap within one period of this end of the array,
+    /// so the array may continue past it
+    GapAdjacent(Side),
+    /// The array is within one period of this end of a linear sequence, so
+    /// repeats may have been lost off the end of the contig
+    Truncated(Side),
+    /// The array overlaps soft-masked (lowercase) bases in the source sequence
+    SoftMasked,
+}
+
+impl ArrayFlag {
+    /// Whether the flag means the array may be missing repeats
+    pub fn possibly_incomplete(&self) -> bool {
+        matches!(self, Self::GapWithin | Self::GapAdjacent(_) | Self::Truncated(_))
+    }
+}
+
+impl fmt::Display for ArrayFlag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::GapWithin => f.write_str("gap_within"),
+            Self::GapAdjacent(side) => write!(f, "gap_at_{side}"),
+            Self::Truncated(side) => write!(f, "truncated_at_{side}"),
+            Self::SoftMasked => f.write_str("soft_masked"),
+        }
+    }
+}
+
+/// Flag assembly gaps (runs of N) inside the array or within one period of either end.
+/// `source` is the forward strand of the sequence the array was found in.
+pub fn gap_flags(array: &CRISPRArray, source: &[u8]) -> Vec<ArrayFlag> {
+    let mut flags = Vec::new();
+    if array.spacers.iter().any(|s| s.contains_n()) {
+        flags.push(ArrayFlag::GapWithin);
+    }
+    let period = array.period();
+    let [start, end] = array.location;
+    if source[start.saturating_sub(period) .. start].contains(&b'N') {
+        flags.push(ArrayFlag::GapAdjacent(Side::Start));
+    }
+    if source[end .. (end + period).min(source.len())].contains(&b'N') {
+        flags.push(ArrayFlag::GapAdjacent(Side::End));
+    }
+    flags
+}
+
+/// Flag arrays that start or end within one period of the ends of a linear
+/// sequence of length `source_len`. Circular sequences have no ends to truncate at.
+pub fn truncation_flags(array: &CRISPRArray, source_len: usize) -> Vec<ArrayFlag> {
+    let period = array.period();
+    let [start, end] = array.location;
+    let mut flags = Vec::new();
+    if start < period {
+        flags.push(ArrayFlag::Truncated(Side::Start));
+    }
+    if source_len.saturating_sub(end) < period {
+        flags.push(ArrayFlag::Truncated(Side::End));
+    }
+    flags
+}
+
+/// Flag arrays overlapping soft-masked bases of `source_seq`, the forward strand
+/// of the sequence they were found in. Arrays spanning the origin of a circular
+/// sequence, with an end before their start, are checked on both sides of it.
+pub fn flag_masked(arrays: &mut [CRISPRArray], source_seq: &Seq) {
+    for array in arrays.iter_mut() {
+        let [start, end] = array.location;
+        let masked = match start < end {
+            true => source_seq.masked_in(start, end),
+            false => source_seq.masked_in(start, source_seq.len()) + source_seq.masked_in(0, end),
+        };
+        if masked > 0 && !array.flags.contains(&ArrayFlag::SoftMasked) {
+            array.flags.push(ArrayFlag::SoftMasked);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fasta::Seq;
+    use super::super::tests::{random_dna, TEST_REPEAT};
+
+    #[test]
+    fn gap_flags_finds_gaps() {
+        let contig = [
+            &random_dna(50, 1), &"N".repeat(20), &random_dna(10, 1), TEST_REPEAT, &random_dna(30, 2),
+            TEST_REPEAT, &random_dna(10, 3), &"N".repeat(20), TEST_REPEAT, &random_dna(100, 4)
+        ].concat();
+        let seq = Seq::from_dna(contig.clone()).unwrap();
+        let array = CRISPRArray::from_repeat_starts(&seq, "contig", &[80, 146, 212], 36).unwrap();
+        let result = gap_flags(&array, contig.as_bytes());
+        assert_eq!(result, vec![ArrayFlag::GapWithin, ArrayFlag::GapAdjacent(Side::Start)]);
+    }
+
+    #[test]
+    fn truncation_flags_finds_contig_ends() {
+        let contig = [&random_dna(20, 1), TEST_REPEAT, &random_dna(30, 2), TEST_REPEAT, &random_dna(100, 3)].concat();
+        let seq = Seq::from_dna(contig.clone()).unwrap();
+        let array = CRISPRArray::from_repeat_starts(&seq, "contig", &[20, 86], 36).unwrap();
+        assert_eq!(truncation_flags(&array, contig.len()), vec![ArrayFlag::Truncated(Side::Start)]);
+        assert_eq!(ArrayFlag::Truncated(Side::End).to_string(), "truncated_at_end");
+    }
+
+    #[test]
+    fn flag_masked_finds_masked_arrays() {
+        let masked = [&random_dna(100, 1), TEST_REPEAT, &random_dna(30, 2).to_lowercase(), TEST_REPEAT, &random_dna(100, 3)].concat();
+        let seq = Seq::from_dna(masked).unwrap();
+        let mut arrays = vec![
+            CRISPRArray::from_repeat_starts(&seq, "contig", &[100, 166], 36).unwrap(),
+            CRISPRArray::from_repeat_starts(&seq, "contig", &[0, 166], 36).unwrap(),
+        ];
+        arrays[1].location = [166, 36];
+        flag_masked(&mut arrays, &Seq::from_dna(random_dna(300, 4)).unwrap());
+        assert!(arrays.iter().all(|a| a.flags().is_empty()));
+        flag_masked(&mut arrays, &seq);
+        assert_eq!(arrays[0].flags(), &[ArrayFlag::SoftMasked]);
+        assert!(arrays[1].flags().is_empty());
+        assert!(!ArrayFlag::SoftMasked.possibly_incomplete());
+    }
+}
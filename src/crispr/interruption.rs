@@ -0,0 +1,266 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::align::{fit, identity};
+use crate::slice::SeqSlice;
+
+use super::{circular_location, CRISPRArray, DetectionParams};
+use super::repeat::Repeat;
+
+// Identity between the consensus repeats of two fragments for them to be the same array
+const MIN_CONSENSUS_IDENTITY: f32 = 0.9;
+
+
+/// A large insertion, such as an IS element, inside an array. The insertion
+/// sits in one spacer, which is reported as everything between its flanking repeats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interruption {
+    location: [usize; 2],
+    spacer: usize,
+}
+
+impl Interruption {
+    /// 0-based start and (exclusive) end of the interrupted spacer on the forward
+    /// strand of the source sequence
+    pub fn location(&self) -> [usize; 2] {
+        self.location
+    }
+
+    /// Index of the interrupted spacer in the array
+    pub fn spacer(&self) -> usize {
+        self.spacer
+    }
+
+    pub(crate) fn reverse_complement(&self, n_spacers: usize) -> Self {
+        Self { location: self.location, spacer: n_spacers - 1 - self.spacer }
+    }
+
+    pub(crate) fn into_forward_coords(self, source_len: usize) -> Self {
+        Self { location: [source_len - self.location[1], source_len - self.location[0]], ..self }
+    }
+
+    pub(crate) fn into_circular_coords(self, offset: usize, source_len: usize) -> Self {
+        Self { location: circular_location(self.location, offset, source_len), ..self }
+    }
+}
+
+impl fmt::Display for Interruption {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}", self.location[0], self.location[1])
+    }
+}
+
+/// Join neighbouring arrays with the same consensus repeat that are separated
+/// by more than a spacer but no more than `params.max_insertion_size`. Past
+/// each end of the joined arrays, copies of the consensus beyond such an
+/// insertion are joined on too, as a fragment with too few repeats to have
+/// been called on its own. The joined array records the gap between the
+/// fragments as an interruption.
+///
+/// `arrays` must all be on the strand they were found on. If `source_seq` is
+/// taken from a circular contig, `circular_len` is the contig's length and
+/// fragments that are closer together the other way round the contig are
+/// left apart: they are the two ends of an array spanning the origin.
+pub fn join_interrupted(mut arrays: Vec<CRISPRArray>, source_seq: SeqSlice, source: &[u8], params: &DetectionParams, circular_len: Option<usize>) -> Vec<CRISPRArray> {
+    arrays.sort_by_key(|a| a.location);
+    let mut joined: Vec<CRISPRArray> = Vec::with_capacity(arrays.len());
+    for array in arrays {
+        let last = match joined.last() {
+            Some(last) if is_interrupted(last, &array, params, circular_len) => joined.pop().expect("Checked there is a last array"),
+            _ => {
+                joined.push(array);
+                continue
+            },
+        };
+        match join(&last, &array, source_seq) {
+            Some(j) => joined.push(j),
+            None => joined.extend([last, array]),
+        }
+    }
+
+    for i in 0 .. joined.len() {
+        let lower = i.checked_sub(1).map_or(0, |p| joined[p].location[1]);
+        let upper = joined.get(i + 1).map_or(source.len(), |a| a.location[0]);
+        let array = &joined[i];
+        let before = find_fragment_before(array, source_seq, source, lower, params)
+            .filter(|f| spans_insertion(f.location, array.location, params, circular_len));
+        let array = match before.and_then(|f| join(&f, array, source_seq)) {
+            Some(j) => j,
+            None => array.clone(),
+        };
+        let after = find_fragment_after(&array, source_seq, source, upper, params)
+            .filter(|f| spans_insertion(array.location, f.location, params, circular_len));
+        if let Some(j) = after.and_then(|f| join(&array, &f, source_seq)) {
+            joined[i] = j;
+        } else {
+            joined[i] = array;
+        }
+    }
+    joined
+}
+
+fn is_interrupted(first: &CRISPRArray, second: &CRISPRArray, params: &DetectionParams, circular_len: Option<usize>) -> bool {
+    spans_insertion(first.location, second.location, params, circular_len)
+        && identity(&first.consensus.to_bytes(), &second.consensus.to_bytes()) >= MIN_CONSENSUS_IDENTITY
+}
+
+// Whether the gap between two fragments is more than a spacer but short enough to be an insertion
+fn spans_insertion(first: [usize; 2], second: [usize; 2], params: &DetectionParams, circular_len: Option<usize>) -> bool {
+    let gap = second[0].saturating_sub(first[1]);
+    // Gap between the fragments going the other way round a circular contig.
+    // A joined array longer than the contig would cover some of it twice
+    let gap_round = circular_len.map(|len| len.saturating_sub(second[1] - first[0]));
+    gap > params.max_spacer_size
+        && gap_round.is_none_or(|g| g > gap)
+        && gap <= params.max_insertion_size
+}
+
+// Copies of the consensus before `array`, more than a spacer but no more
+// than an insertion away from it and no earlier than `lower`
+fn find_fragment_before(array: &CRISPRArray, source_seq: SeqSlice, source: &[u8], lower: usize, params: &DetectionParams) -> Option<CRISPRArray> {
+    let consensus = array.consensus.to_bytes();
+    let start = array.location[0].saturating_sub(params.max_insertion_size + consensus.len() + params.max_indels).max(lower);
+    let end = array.location[0].saturating_sub(params.max_spacer_size + 1);
+    let copy = find_copy(source_seq, source, &consensus, start, end, params)?;
+    fragment_around(copy, source_seq, source, &consensus, [lower, end], &array.source_name, params)
+}
+
+// Copies of the consensus after `array`, more than a spacer but no more
+// than an insertion away from it and ending no later than `upper`
+fn find_fragment_after(array: &CRISPRArray, source_seq: SeqSlice, source: &[u8], upper: usize, params: &DetectionParams) -> Option<CRISPRArray> {
+    let consensus = array.consensus.to_bytes();
+    let start = array.location[1] + params.max_spacer_size + 1;
+    let end = (array.location[1] + params.max_insertion_size + consensus.len() + params.max_indels).min(upper);
+    let copy = find_copy(source_seq, source, &consensus, start, end, params)?;
+    fragment_around(copy, source_seq, source, &consensus, [start, upper], &array.source_name, params)
+}
+
+// Extend a copy of the consensus one spacer at a time in both directions,
+// staying within `bounds`
+fn fragment_around(copy: Repeat, source_seq: SeqSlice, source: &[u8], consensus: &[u8], bounds: [usize; 2], source_name: &str, params: &DetectionParams) -> Option<CRISPRArray> {
+    let period = params.max_spacer_size + consensus.len() + params.max_indels;
+    let mut repeats = VecDeque::from([copy]);
+    while let Some(rep) = repeats.front()
+        .and_then(|first| {
+            let end = first.start().checked_sub(params.min_spacer_size)?;
+            find_copy(source_seq, source, consensus, first.start().saturating_sub(period).max(bounds[0]), end, params)
+        }) {
+        repeats.push_front(rep);
+    }
+    while let Some(rep) = repeats.back()
+        .and_then(|last| {
+            let start = last.end() + params.min_spacer_size;
+            find_copy(source_seq, source, consensus, start, (last.end() + period).min(bounds[1]), params)
+        }) {
+        repeats.push_back(rep);
+    }
+    match repeats.len() {
+        1 => CRISPRArray::from_single_repeat(source_name, repeats.pop_front()?).ok(),
+        _ => CRISPRArray::from_repeats(source_seq, source_name, repeats.into()).ok(),
+    }
+}
+
+// The best copy of the consensus in `source[start .. end]`, if it's within
+// the edits allowed between a repeat and the consensus
+fn find_copy(source_seq: SeqSlice, source: &[u8], consensus: &[u8], start: usize, end: usize, params: &DetectionParams) -> Option<Repeat> {
+    if end <= start || end - start < consensus.len() {
+        return None
+    }
+    let aln = fit(consensus, &source[start .. end]);
+    if aln.mismatches() > params.max_mismatches || aln.indels() > params.max_indels {
+        return None
+    }
+    Repeat::from_alignment(source_seq, consensus, source, &aln, start).ok()
+}
+
+// Join two fragments of an array. The boundary shift is the one found for
+// the first array called by seeding, as short fragments don't have one
+fn join(first: &CRISPRArray, second: &CRISPRArray, source_seq: SeqSlice) -> Option<CRISPRArray> {
+    let repeats = first.repeats.iter().chain(second.repeats.iter()).cloned().collect();
+    let mut array = CRISPRArray::from_repeats(source_seq, &first.source_name, repeats).ok()?;
+    let offset = first.spacers.len() + 1;
+    array.interruptions = first.interruptions.iter().copied()
+        .chain([Interruption { location: [first.location[1], second.location[0]], spacer: first.spacers.len() }])
+        .chain(second.interruptions.iter().map(|i| Interruption { spacer: i.spacer + offset, ..*i }))
+        .collect();
+    array.add_supporting_k(&first.supporting_k);
+    array.add_supporting_k(&second.supporting_k);
+    array.boundary_shift = match first.supporting_k.is_empty() {
+        true => second.boundary_shift,
+        false => first.boundary_shift,
+    };
+    array.merged_calls = first.merged_calls.iter().chain(&second.merged_calls).copied().collect();
+    array.update_confidence();
+    Some(array)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fasta::Seq;
+    use super::super::find_crisprs;
+    use super::super::tests::{random_dna, synthetic_array, TEST_REPEAT};
+
+    #[test]
+    fn joins_array_interrupted_by_insertion() {
+        let fragment = synthetic_array(TEST_REPEAT, 3, 30, 0);
+        let contig = [
+            random_dna(300, 1), fragment.clone(), random_dna(1500, 2), fragment.clone(), random_dna(300, 3)
+        ].concat();
+        let seq = Seq::from_dna(contig).unwrap();
+        let arrays = find_crisprs(&seq, "contig", &DetectionParams::default()).unwrap();
+        assert_eq!(arrays.len(), 1);
+        assert_eq!(arrays[0].repeats().len(), 8);
+        assert_eq!(arrays[0].interruptions().len(), 1);
+        let gap_start = 300 + fragment.len();
+        assert_eq!(arrays[0].interruptions()[0].location(), [gap_start, gap_start + 1500]);
+        assert_eq!(arrays[0].spacers()[arrays[0].interruptions()[0].spacer()].len(), 1500);
+    }
+
+    #[test]
+    fn joins_short_fragment_after_insertion() {
+        let fragment = synthetic_array(TEST_REPEAT, 4, 30, 0);
+        let short = synthetic_array(TEST_REPEAT, 1, 30, 0);
+        let contig = [
+            random_dna(300, 1), fragment.clone(), random_dna(1500, 2), short, random_dna(300, 3)
+        ].concat();
+        let seq = Seq::from_dna(contig).unwrap();
+        let arrays = find_crisprs(&seq, "contig", &DetectionParams::default()).unwrap();
+        assert_eq!(arrays.len(), 1);
+        assert_eq!(arrays[0].repeats().len(), 7);
+        let gap_start = 300 + fragment.len();
+        assert_eq!(arrays[0].interruptions().len(), 1);
+        assert_eq!(arrays[0].interruptions()[0].location(), [gap_start, gap_start + 1500]);
+    }
+
+    #[test]
+    fn joins_short_fragment_before_insertion() {
+        let short = synthetic_array(TEST_REPEAT, 1, 30, 0);
+        let fragment = synthetic_array(TEST_REPEAT, 4, 30, 0);
+        let contig = [
+            random_dna(300, 1), short.clone(), random_dna(1500, 2), fragment, random_dna(300, 3)
+        ].concat();
+        let seq = Seq::from_dna(contig).unwrap();
+        let arrays = find_crisprs(&seq, "contig", &DetectionParams::default()).unwrap();
+        assert_eq!(arrays.len(), 1);
+        assert_eq!(arrays[0].repeats().len(), 7);
+        assert_eq!(arrays[0].location()[0], 300);
+        let gap_start = 300 + short.len();
+        assert_eq!(arrays[0].interruptions().len(), 1);
+        assert_eq!(arrays[0].interruptions()[0].location(), [gap_start, gap_start + 1500]);
+    }
+
+    #[test]
+    fn keeps_distant_arrays_apart() {
+        let fragment = synthetic_array(TEST_REPEAT, 3, 30, 0);
+        let contig = [
+            random_dna(300, 1), fragment.clone(), random_dna(1500, 2), fragment.clone(), random_dna(300, 3)
+        ].concat();
+        let seq = Seq::from_dna(contig).unwrap();
+        let params = DetectionParams { max_insertion_size: 1000, ..Default::default() };
+        let arrays = find_crisprs(&seq, "contig", &params).unwrap();
+        assert_eq!(arrays.len(), 2);
+        assert!(arrays.iter().all(|a| a.interruptions().is_empty()));
+    }
+}
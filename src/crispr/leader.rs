@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+
+use crate::align::identity;
+use crate::fasta::Seq;
+
+use super::orientation::at_content;
+use super::{CRISPRArray, Strand};
+
+
+/// Sequence upstream of the first repeat, on the strand the array is reported on.
+/// When the array's orientation was predicted this is the leader side.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Leader {
+    seq: Seq,
+    location: [usize; 2],
+    at_content: f64,
+    similarity: Option<f64>,
+}
+
+impl Leader {
+    /// Leader read in the same direction as the array's repeats
+    pub fn seq(&self) -> &Seq {
+        &self.seq
+    }
+
+    /// 0-based start and (exclusive) end on the forward strand of the source sequence
+    pub fn location(&self) -> [usize; 2] {
+        self.location
+    }
+
+    pub fn at_content(&self) -> f64 {
+        self.at_content
+    }
+
+    /// Highest identity to the leader of another array with the same consensus
+    /// repeat. `None` until compared, or if there is no such array
+    pub fn similarity(&self) -> Option<f64> {
+        self.similarity
+    }
+}
+
+/// Take up to `length` bases upstream of the first repeat of each array as
+/// its leader. Arrays at the very start of their sequence have no leader.
+/// `source_seq` is the forward strand of the sequence the arrays were found in.
+pub fn add_leaders(arrays: &mut [CRISPRArray], source_seq: &Seq, length: usize) {
+    for array in arrays.iter_mut() {
+        array.leader = extract(array, source_seq, length);
+    }
+}
+
+fn extract(array: &CRISPRArray, source_seq: &Seq, length: usize) -> Option<Leader> {
+    let [start, end] = array.location;
+    let location = match array.strand {
+        Strand::Forward => [start.saturating_sub(length), start],
+        Strand::Reverse => [end, (end + length).min(source_seq.len())],
+    };
+    if location[0] >= location[1] {
+        return None
+    }
+    let view = source_seq.slice(location[0], location[1]).ok()?;
+    let seq = match array.strand {
+        Strand::Forward => view.to_seq(),
+        Strand::Reverse => view.rev_comp().to_seq(),
+    };
+    let at_content = at_content(&seq.to_bytes())?;
+    Some(Leader { seq, location, at_content, similarity: None })
+}
+
+/// Compare the leaders of arrays sharing a consensus repeat, in either
+/// orientation, and record each leader's best identity to another. Arrays can
+/// come from different sequences.
+pub fn compare_leaders(arrays: &mut [CRISPRArray]) {
+    let mut groups: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, array) in arrays.iter().enumerate() {
+        if array.leader.is_some() {
+            groups.entry(canonical(&array.consensus)).or_default().push(i);
+        }
+    }
+    for members in groups.values() {
+        let leaders: Vec<Vec<u8>> = members.iter()
+            .map(|i| arrays[*i].leader.as_ref().expect("Grouped arrays have leaders").seq.to_bytes())
+            .collect();
+        for (a, i) in members.iter().enumerate() {
+            let best = leaders.iter().enumerate()
+                .filter(|(b, _)| *b != a)
+                .map(|(_, other)| identity(&leaders[a], other) as f64)
+                .fold(None, |best: Option<f64>, id| Some(best.map_or(id, |b| b.max(id))));
+            if let Some(leader) = arrays[*i].leader.as_mut() {
+                leader.similarity = best;
+            }
+        }
+    }
+}
+
+// The same repeat read from either strand gives the same key
+fn canonical(repeat: &Seq) -> String {
+    let fwd = repeat.to_string();
+    let rev = repeat.rev_comp().to_string();
+    fwd.min(rev)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::{random_dna, synthetic_array, TEST_REPEAT};
+
+    #[test]
+    fn extracts_leader_on_reported_strand() {
+        let contig = synthetic_array(TEST_REPEAT, 2, 30, 100);
+        let seq = Seq::from_dna(contig.clone()).unwrap();
+        let array = CRISPRArray::from_repeat_starts(&seq, "contig", &[100, 166, 232], 36).unwrap();
+        let leader = extract(&array, &seq, 50).unwrap();
+        assert_eq!(leader.location(), [50, 100]);
+        assert_eq!(leader.seq().to_string(), contig[50..100]);
+
+        let rc = array.reverse_complement();
+        let leader = extract(&rc, &seq, 500).unwrap();
+        assert_eq!(leader.location(), [268, contig.len()]);
+        assert_eq!(leader.seq(), &Seq::from_dna(contig[268..].to_string()).unwrap().rev_comp());
+    }
+
+    #[test]
+    fn compares_leaders_of_same_repeat() {
+        let leader = random_dna(100, 7);
+        let array = synthetic_array(TEST_REPEAT, 2, 30, 0);
+        let contig = [leader.as_str(), &array, &random_dna(300, 8), &leader, &array].concat();
+        let seq = Seq::from_dna(contig).unwrap();
+        let second = 100 + array.len() + 300 + 100;
+        let mut arrays = vec![
+            CRISPRArray::from_repeat_starts(&seq, "contig", &[100, 166, 232], 36).unwrap(),
+            CRISPRArray::from_repeat_starts(&seq, "contig", &[second, second + 66, second + 132], 36).unwrap(),
+        ];
+        add_leaders(&mut arrays, &seq, 100);
+        compare_leaders(&mut arrays);
+        assert_eq!(arrays[0].leader().unwrap().similarity(), Some(1.0));
+        assert_eq!(arrays[1].leader().unwrap().similarity(), Some(1.0));
+    }
+}
@@ -0,0 +1,313 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+
+use crate::align::{fit, identity, FitAlignment};
+use crate::fasta::{Fasta, Seq};
+
+use super::{flags, CRISPRArray, DetectionParams, Strand};
+use super::repeat::Repeat;
+
+// Identity to a library repeat, in either orientation, needed to name a de novo array's consensus
+const MIN_LIBRARY_IDENTITY: f32 = 0.9;
+
+
+/// A named repeat from a library of known CRISPR repeats, written in the
+/// orientation it is transcribed in
+#[derive(Debug, Clone, PartialEq)]
+pub struct LibraryRepeat {
+    name: String,
+    seq: Seq,
+}
+
+impl LibraryRepeat {
+    pub fn new(name: &str, seq: Seq) -> Self {
+        Self { name: name.to_string(), seq }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn seq(&self) -> &Seq {
+        &self.seq
+    }
+}
+
+/// Known CRISPR repeats used to guide detection
+#[derive(Debug, Clone, Default)]
+pub struct RepeatLibrary {
+    repeats: Vec<LibraryRepeat>,
+}
+
+// constructors
+impl RepeatLibrary {
+    pub fn new(mut repeats: Vec<LibraryRepeat>) -> Self {
+        repeats.sort_by(|a, b| a.name.cmp(&b.name));
+        Self { repeats }
+    }
+
+    /// Read a library from a FASTA file. Each repeat is named by the first word of its header
+    pub fn from_file(file: &str) -> Result<Self> {
+        let fasta = Fasta::from_file(file)?;
+        let repeats: Vec<LibraryRepeat> = fasta.iter()
+            .map(|(header, seq)| {
+                let name = header.split_whitespace().next().unwrap_or(header);
+                LibraryRepeat::new(name, seq.clone())
+            })
+            .collect();
+        if let Some(r) = repeats.iter().find(|r| r.seq.is_empty()) {
+            return Err(anyhow!("library repeat {} is empty", r.name))
+        }
+        Ok(Self::new(repeats))
+    }
+}
+
+// methods
+impl RepeatLibrary {
+    pub fn repeats(&self) -> &[LibraryRepeat] {
+        &self.repeats
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.repeats.is_empty()
+    }
+
+    /// Name of the library repeat most similar to `repeat` in either orientation,
+    /// if any is at least `MIN_LIBRARY_IDENTITY` identical
+    pub fn best_match(&self, repeat: &Seq) -> Option<&str> {
+        let fwd = repeat.to_bytes();
+        let rev = repeat.rev_comp().to_bytes();
+        self.repeats.iter()
+            .map(|r| {
+                let lib = r.seq.to_bytes();
+                (r, identity(&fwd, &lib).max(identity(&rev, &lib)))
+            })
+            .filter(|(_, id)| *id >= MIN_LIBRARY_IDENTITY)
+            .fold(None, |best: Option<(&LibraryRepeat, f32)>, (r, id)| match best {
+                Some((_, best_id)) if best_id >= id => best,
+                _ => Some((r, id)),
+            })
+            .map(|(r, _)| r.name())
+    }
+}
+
+
+// A copy of a library repeat found in the source sequence
+#[derive(Debug, Clone)]
+struct Hit {
+    entry: usize,
+    strand: Strand,
+    aln: FitAlignment,
+}
+
+impl Hit {
+    fn overlaps(&self, other: &Hit) -> bool {
+        self.aln.start < other.aln.end && other.aln.start < self.aln.end
+    }
+}
+
+/// Find arrays made of copies of library repeats. Copies may differ from the
+/// library repeat by up to the maximum mismatches and indels in `params`.
+/// Copies of the same repeat separated by an acceptable spacer length are
+/// grouped into arrays, so arrays of two repeats and lone orphan repeats are
+/// reported too. Every array is named after its library repeat and reported
+/// on the strand the library repeat matched.
+///
+/// Unlike de novo detection the candidates aren't validated, as matching a
+/// known repeat is the evidence for the call.
+pub fn find_library_arrays(source_seq: &Seq, source_name: &str, library: &RepeatLibrary, params: &DetectionParams) -> Vec<CRISPRArray> {
+    let source = source_seq.to_bytes();
+    let mut hits: Vec<Hit> = Vec::new();
+    for (entry, lib) in library.repeats.iter().enumerate() {
+        for strand in [Strand::Forward, Strand::Reverse] {
+            let query = match strand {
+                Strand::Forward => lib.seq.to_bytes(),
+                Strand::Reverse => lib.seq.rev_comp().to_bytes(),
+            };
+            hits.extend(find_copies(&source, &query, params).into_iter()
+                .map(|aln| Hit { entry, strand, aln }));
+        }
+    }
+    let hits = best_non_overlapping(hits);
+
+    let mut arrays = Vec::new();
+    let mut group: Vec<Hit> = Vec::new();
+    for hit in hits {
+        let joins = group.last().is_some_and(|last| {
+            let spacer = hit.aln.start.saturating_sub(last.aln.end);
+            last.entry == hit.entry && last.strand == hit.strand
+                && (params.min_spacer_size ..= params.max_spacer_size).contains(&spacer)
+        });
+        if !joins && !group.is_empty() {
+            arrays.extend(build_array(source_seq, &source, source_name, library, &group));
+            group.clear();
+        }
+        group.push(hit);
+    }
+    if !group.is_empty() {
+        arrays.extend(build_array(source_seq, &source, source_name, library, &group));
+    }
+    arrays
+}
+
+/// Name de novo arrays whose consensus repeat matches a library repeat
+pub fn annotate(arrays: &mut [CRISPRArray], library: &RepeatLibrary) {
+    for array in arrays.iter_mut() {
+        array.library_match = library.best_match(&array.consensus).map(str::to_string);
+    }
+}
+
+/// Flag library calls truncated at the ends of a linear sequence of length `source_len`
+pub fn flag_truncated(arrays: &mut [CRISPRArray], source_len: usize) {
+    for array in arrays.iter_mut() {
+        array.flags.extend(flags::truncation_flags(array, source_len));
+    }
+}
+
+/// Combine de novo and library calls for a sequence. Library calls overlapping
+/// a de novo call are dropped as the de novo call already covers that array.
+pub fn merge_calls(mut denovo: Vec<CRISPRArray>, library: Vec<CRISPRArray>) -> Vec<CRISPRArray> {
+    let covered: Vec<[usize; 2]> = denovo.iter().map(|a| a.location).collect();
+    denovo.extend(library.into_iter()
+        .filter(|l| !covered.iter().any(|[start, end]| l.location[0] < *end && *start < l.location[1])));
+    denovo
+}
+
+// Approximate copies of `query` in `source`. An alignment within the allowed
+// edits must contain one of edits + 1 equal pieces of the query exactly, so
+// exact matches of those pieces seed the alignments.
+fn find_copies(source: &[u8], query: &[u8], params: &DetectionParams) -> Vec<FitAlignment> {
+    let max_edits = params.max_mismatches + params.max_indels;
+    let piece_len = query.len() / (max_edits + 1);
+    if piece_len == 0 || source.len() < query.len() {
+        return Vec::new()
+    }
+    let mut pieces: HashMap<&[u8], Vec<usize>> = HashMap::new();
+    for offset in (0 ..= max_edits).map(|i| i * piece_len) {
+        pieces.entry(&query[offset .. offset + piece_len]).or_default().push(offset);
+    }
+
+    let mut candidates: Vec<usize> = Vec::new();
+    for (pos, window) in source.windows(piece_len).enumerate() {
+        if let Some(offsets) = pieces.get(window) {
+            candidates.extend(offsets.iter()
+                .filter(|o| pos >= **o)
+                .map(|o| pos - o));
+        }
+    }
+    candidates.sort();
+    candidates.dedup();
+
+    let mut copies: Vec<FitAlignment> = Vec::new();
+    for cand in candidates {
+        let start = cand.saturating_sub(params.max_indels);
+        let end = (cand + query.len() + params.max_indels).min(source.len());
+        let mut aln = fit(query, &source[start .. end]);
+        if aln.mismatches() > params.max_mismatches || aln.indels() > params.max_indels {
+            continue
+        }
+        aln.start += start;
+        aln.end += start;
+        // Nearby seeds find the same copy
+        match copies.last() {
+            Some(last) if last.start == aln.start && last.end == aln.end => (),
+            Some(last) if aln.start < last.end && aln.edits() < last.edits() => {
+                copies.pop();
+                copies.push(aln);
+            },
+            Some(last) if aln.start < last.end => (),
+            _ => copies.push(aln),
+        }
+    }
+    copies
+}
+
+// Where copies of different library repeats overlap keep the closest match,
+// then order what remains by position
+fn best_non_overlapping(mut hits: Vec<Hit>) -> Vec<Hit> {
+    hits.sort_by_key(|h| (h.aln.edits(), h.aln.start));
+    let mut kept: Vec<Hit> = Vec::with_capacity(hits.len());
+    for hit in hits {
+        if !kept.iter().any(|k| k.overlaps(&hit)) {
+            kept.push(hit);
+        }
+    }
+    kept.sort_by_key(|h| h.aln.start);
+    kept
+}
+
+fn build_array(source_seq: &Seq, source: &[u8], source_name: &str, library: &RepeatLibrary, group: &[Hit]) -> Option<CRISPRArray> {
+    let lib = &library.repeats[group[0].entry];
+    let strand = group[0].strand;
+    let query = match strand {
+        Strand::Forward => lib.seq.to_bytes(),
+        Strand::Reverse => lib.seq.rev_comp().to_bytes(),
+    };
+    let repeats = group.iter()
+        .map(|h| {
+            let aln = FitAlignment { start: 0, end: h.aln.end - h.aln.start, ops: h.aln.ops.clone() };
+            Repeat::from_alignment(source_seq, &query, source, &aln, h.aln.start)
+        })
+        .collect::<Result<Vec<Repeat>>>()
+        .ok()?;
+    let mut array = match repeats.len() {
+        1 => CRISPRArray::from_single_repeat(source_name, repeats.into_iter().next()?),
+        _ => CRISPRArray::from_repeats(source_seq, source_name, repeats),
+    }.ok()?;
+    array.library_match = Some(lib.name.clone());
+    array.orientation = Some(strand);
+    if strand == Strand::Reverse {
+        array = array.reverse_complement();
+    }
+    array.flags.extend(flags::gap_flags(&array, source));
+    array.update_confidence();
+    Some(array)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::{random_dna, TEST_REPEAT};
+
+    fn library() -> RepeatLibrary {
+        RepeatLibrary::new(vec![
+            LibraryRepeat::new("SpyCas9", Seq::from_dna(TEST_REPEAT.to_string()).unwrap()),
+            LibraryRepeat::new("random", Seq::from_dna(random_dna(30, 99)).unwrap()),
+        ])
+    }
+
+    #[test]
+    fn finds_two_repeat_array_and_orphan() {
+        let mut mutated = TEST_REPEAT.to_string().into_bytes();
+        mutated[10] = b'A';
+        let mutated = String::from_utf8(mutated).unwrap();
+        let orphan = Seq::from_dna(TEST_REPEAT.to_string()).unwrap().rev_comp().to_string();
+        let contig = [
+            &random_dna(200, 1), TEST_REPEAT, &random_dna(30, 2), &mutated,
+            &random_dna(500, 3), &orphan, &random_dna(200, 4),
+        ].concat();
+        let seq = Seq::from_dna(contig).unwrap();
+        let arrays = find_library_arrays(&seq, "contig", &library(), &DetectionParams::default());
+        assert_eq!(arrays.len(), 2);
+
+        assert_eq!(arrays[0].repeats().len(), 2);
+        assert_eq!(arrays[0].location(), [200, 200 + 36 + 30 + 36]);
+        assert_eq!(arrays[0].library_match(), Some("SpyCas9"));
+        assert_eq!(arrays[0].strand(), Strand::Forward);
+
+        assert_eq!(arrays[1].repeats().len(), 1);
+        assert!(arrays[1].spacers().is_empty());
+        assert_eq!(arrays[1].strand(), Strand::Reverse);
+        assert_eq!(arrays[1].repeats()[0].to_string(), TEST_REPEAT);
+    }
+
+    #[test]
+    fn best_match_checks_both_orientations() {
+        let lib = library();
+        let rc = Seq::from_dna(TEST_REPEAT.to_string()).unwrap().rev_comp();
+        assert_eq!(lib.best_match(&rc), Some("SpyCas9"));
+        assert_eq!(lib.best_match(&Seq::from_dna(random_dna(36, 5)).unwrap()), None);
+    }
+}
@@ -0,0 +1,136 @@
+use std::fmt;
+
+use crate::fasta::complement;
+
+use super::CRISPRArray;
+
+// Bases either side of the array compared for AT content
+const LEADER_WINDOW: usize = 150;
+// Minimum difference in AT content between the flanks to call a leader side
+const MIN_AT_DIFFERENCE: f64 = 0.05;
+// Repeats in this orientation often end in this motif just before the spacer
+const TERMINAL_MOTIFS: [&[u8]; 2] = [b"GAAAC", b"GAAAG"];
+const TERMINAL_MOTIF_CORE: [&[u8]; 2] = [b"AAAC", b"AAAG"];
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Strand {
+    Forward,
+    Reverse,
+}
+
+impl Strand {
+    pub fn opposite(&self) -> Self {
+        match self {
+            Self::Forward => Self::Reverse,
+            Self::Reverse => Self::Forward,
+        }
+    }
+}
+
+impl fmt::Display for Strand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Forward => f.write_str("+"),
+            Self::Reverse => f.write_str("-"),
+        }
+    }
+}
+
+/// Predict the strand an array is transcribed from. Each piece of evidence
+/// votes for the strand the array is currently reported on (positive) or the
+/// opposite strand (negative):
+/// - the consensus repeat ends in the conserved 3' motif (e.g. GAAAC)
+/// - the flank before the first repeat (the leader) is more AT rich than the flank after the last
+/// - repeats become more degenerate towards the trailer end
+///
+/// `source` is the forward strand of the sequence the array was found in.
+/// Returns `None` when the evidence is balanced.
+pub fn predict(array: &CRISPRArray, source: &[u8]) -> Option<Strand> {
+    let score = motif_vote(&array.consensus.to_bytes())
+        + leader_vote(array, source)
+        + degeneracy_vote(array);
+    match score {
+        s if s > 0 => Some(array.strand),
+        s if s < 0 => Some(array.strand.opposite()),
+        _ => None,
+    }
+}
+
+// Motif at the 3' end of the consensus votes for this orientation, its reverse
+// complement at the 5' end votes against. The full motif outweighs the core.
+fn motif_vote(consensus: &[u8]) -> i32 {
+    let rc: Vec<u8> = consensus.iter().rev().map(|b| complement(*b)).collect();
+    motif_strength(consensus) - motif_strength(&rc)
+}
+
+fn motif_strength(repeat: &[u8]) -> i32 {
+    if TERMINAL_MOTIFS.iter().any(|m| repeat.ends_with(m)) {
+        2
+    } else if TERMINAL_MOTIF_CORE.iter().any(|m| repeat.ends_with(m)) {
+        1
+    } else {
+        0
+    }
+}
+
+fn leader_vote(array: &CRISPRArray, source: &[u8]) -> i32 {
+    let [start, end] = array.location;
+    let before = &source[start.saturating_sub(LEADER_WINDOW) .. start];
+    let after = &source[end .. (end + LEADER_WINDOW).min(source.len())];
+    let (before, after) = match (at_content(before), at_content(after)) {
+        (Some(b), Some(a)) => (b, a),
+        _ => return 0,
+    };
+    // Which flank is upstream depends on the strand the array is reported on
+    let (upstream, downstream) = match array.strand {
+        Strand::Forward => (before, after),
+        Strand::Reverse => (after, before),
+    };
+    match upstream - downstream {
+        d if d > MIN_AT_DIFFERENCE => 1,
+        d if d < -MIN_AT_DIFFERENCE => -1,
+        _ => 0,
+    }
+}
+
+// Compare the number of variants in the first and second half of the array
+fn degeneracy_vote(array: &CRISPRArray) -> i32 {
+    let half = array.repeats.len() / 2;
+    let leading: usize = array.repeats[.. half].iter().map(|r| r.variants().len()).sum();
+    let trailing: usize = array.repeats[array.repeats.len() - half ..].iter().map(|r| r.variants().len()).sum();
+    match trailing.cmp(&leading) {
+        std::cmp::Ordering::Greater => 1,
+        std::cmp::Ordering::Less => -1,
+        std::cmp::Ordering::Equal => 0,
+    }
+}
+
+/// Proportion of A and T among the non-N bases
+pub(crate) fn at_content(bases: &[u8]) -> Option<f64> {
+    let called: Vec<&u8> = bases.iter().filter(|b| **b != b'N').collect();
+    if called.is_empty() {
+        return None
+    }
+    let at = called.iter().filter(|b| matches!(***b, b'A' | b'T')).count();
+    Some(at as f64 / called.len() as f64)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn motif_vote_works() {
+        assert_eq!(motif_vote(b"CGGTTCACTGCCGTACAGGCAGCTTAGAAAC"), 2);
+        assert_eq!(motif_vote(b"GTTTCTAAGCTGCCTGTACGGCAGTGAACCG"), -2);
+        assert_eq!(motif_vote(b"ATCGATCGATCG"), 0);
+    }
+
+    #[test]
+    fn at_content_ignores_n() {
+        assert_eq!(at_content(b"ATNNGC"), Some(0.5));
+        assert_eq!(at_content(b"NNN"), None);
+    }
+}
@@ -0,0 +1,135 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use super::{circular_location, CRISPRArray, Strand};
+
+
+/// A call that overlapped a better supported call of the same locus and was
+/// merged into it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MergedCall {
+    location: [usize; 2],
+    strand: Strand,
+    confidence: f64,
+}
+
+impl MergedCall {
+    fn from_array(array: &CRISPRArray) -> Self {
+        Self { location: array.location, strand: array.strand, confidence: array.confidence }
+    }
+
+    /// 0-based start and (exclusive) end of the merged call on the forward strand
+    pub fn location(&self) -> [usize; 2] {
+        self.location
+    }
+
+    pub fn strand(&self) -> Strand {
+        self.strand
+    }
+
+    pub fn confidence(&self) -> f64 {
+        self.confidence
+    }
+
+    pub(crate) fn into_forward_coords(self, source_len: usize) -> Self {
+        Self {
+            location: [source_len - self.location[1], source_len - self.location[0]],
+            strand: self.strand.opposite(),
+            ..self
+        }
+    }
+
+    pub(crate) fn into_circular_coords(self, offset: usize, source_len: usize) -> Self {
+        Self { location: circular_location(self.location, offset, source_len), ..self }
+    }
+}
+
+impl fmt::Display for MergedCall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}({})", self.location[0], self.location[1], self.strand)
+    }
+}
+
+/// Reduce calls that overlap, from either strand, different seeds or different
+/// k-mer sizes, to one call per locus. Overlapping calls are grouped and the
+/// best call of each group is kept, by:
+/// 1. highest confidence
+/// 2. most repeats
+/// 3. forward strand
+/// 4. leftmost start
+///
+/// The kept call lists the others as merged calls and gains their supporting k-mer sizes.
+/// Confidence must be up to date.
+pub fn resolve_overlaps(mut arrays: Vec<CRISPRArray>) -> Vec<CRISPRArray> {
+    arrays.sort_by_key(|a| a.location);
+    let mut groups: Vec<Vec<CRISPRArray>> = Vec::new();
+    let mut group_end = 0usize;
+    for array in arrays {
+        match groups.last_mut() {
+            Some(group) if array.location[0] < group_end => {
+                group_end = group_end.max(array.location[1]);
+                group.push(array);
+            },
+            _ => {
+                group_end = array.location[1];
+                groups.push(vec![array]);
+            },
+        }
+    }
+    groups.into_iter()
+        .map(merge_group)
+        .collect()
+}
+
+fn merge_group(group: Vec<CRISPRArray>) -> CRISPRArray {
+    let best = (0..group.len())
+        .max_by(|a, b| compare(&group[*a], &group[*b]))
+        .expect("Groups are never empty");
+    let mut group = group;
+    let mut kept = group.swap_remove(best);
+    for other in group {
+        kept.add_supporting_k(&other.supporting_k);
+        kept.merged_calls.push(MergedCall::from_array(&other));
+        kept.merged_calls.extend(other.merged_calls);
+    }
+    // Passes that made exactly the same call aren't worth listing
+    let (location, strand) = (kept.location, kept.strand);
+    kept.merged_calls.retain(|m| m.location != location || m.strand != strand);
+    kept.merged_calls.sort_by_key(|m| (m.location, m.strand == Strand::Reverse));
+    kept.merged_calls.dedup_by(|a, b| a.location == b.location && a.strand == b.strand);
+    kept
+}
+
+// Greater is better
+fn compare(a: &CRISPRArray, b: &CRISPRArray) -> Ordering {
+    a.confidence.total_cmp(&b.confidence)
+        .then(a.repeats.len().cmp(&b.repeats.len()))
+        .then((a.strand == Strand::Forward).cmp(&(b.strand == Strand::Forward)))
+        .then(b.location[0].cmp(&a.location[0]))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fasta::Seq;
+    use super::super::tests::{synthetic_array, TEST_REPEAT};
+
+    #[test]
+    fn keeps_best_call_and_records_others() {
+        let seq = Seq::from_dna(synthetic_array(TEST_REPEAT, 4, 30, 100)).unwrap();
+        let starts = [100, 166, 232, 298, 364];
+        let mut full = CRISPRArray::from_repeat_starts(&seq, "contig", &starts, 36).unwrap();
+        let mut part = CRISPRArray::from_repeat_starts(&seq, "contig", &starts[1..4], 36).unwrap();
+        let mut rc = full.reverse_complement();
+        for array in [&mut full, &mut part, &mut rc] {
+            array.update_confidence();
+        }
+        let result = resolve_overlaps(vec![part, rc, full]);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].strand(), Strand::Forward);
+        assert_eq!(result[0].repeats().len(), 5);
+        let merged: Vec<String> = result[0].merged_calls().iter().map(|m| m.to_string()).collect();
+        assert_eq!(merged, vec!["100-400(-)", "166-334(+)"]);
+    }
+}
@@ -0,0 +1,108 @@
+use anyhow::{anyhow, Result};
+
+
+/// Settings that control array detection and validation
+#[derive(Debug, Clone, PartialEq)]
+pub struct DetectionParams {
+    /// Sizes of the seed k-mers used to find candidate repeats. Detection
+    /// runs once for each size and the calls are merged
+    pub kmer_sizes: Vec<usize>,
+    /// Minimum number of repeats to call an array
+    pub min_reps: usize,
+    pub min_rep_size: usize,
+    pub max_rep_size: usize,
+    pub min_spacer_size: usize,
+    pub max_spacer_size: usize,
+    /// Maximum proportion difference in repeat lengths in an array
+    pub max_rep_len_dev: f32,
+    /// Maximum proportion difference in spacer lengths in an array
+    pub max_spacer_len_dev: f32,
+    /// Maximum mismatches between a repeat copy and the consensus
+    pub max_mismatches: usize,
+    /// Maximum inserted or deleted bases between a repeat copy and the consensus
+    pub max_indels: usize,
+    /// Longest insertion (e.g. an IS element) allowed between two fragments of one array
+    pub max_insertion_size: usize,
+    /// Don't seed repeats from k-mers that include soft-masked (lowercase) bases
+    pub skip_masked: bool,
+}
+
+impl Default for DetectionParams {
+    fn default() -> Self {
+        Self {
+            kmer_sizes: vec![11],
+            min_reps: 3,
+            min_rep_size: 20,
+            max_rep_size: 70,
+            min_spacer_size: 20,
+            max_spacer_size: 70,
+            max_rep_len_dev: 0.1,
+            max_spacer_len_dev: 0.1,
+            max_mismatches: 3,
+            max_indels: 1,
+            max_insertion_size: 10_000,
+            skip_masked: false,
+        }
+    }
+}
+
+impl DetectionParams {
+    /// Check the settings are consistent with each other
+    pub fn validate(&self) -> Result<()> {
+        if self.kmer_sizes.is_empty() {
+            return Err(anyhow!("at least one k-mer size is needed"))
+        }
+        for k in &self.kmer_sizes {
+            if *k < 4 {
+                return Err(anyhow!("k-mer size must be at least 4"))
+            }
+            if *k > self.min_rep_size {
+                return Err(anyhow!("k-mer size ({k}) must not be larger than the minimum repeat size ({})", self.min_rep_size))
+            }
+        }
+        if self.min_reps < 2 {
+            return Err(anyhow!("minimum number of repeats must be at least 2"))
+        }
+        if self.min_rep_size > self.max_rep_size {
+            return Err(anyhow!("minimum repeat size ({}) is larger than the maximum ({})", self.min_rep_size, self.max_rep_size))
+        }
+        if self.min_spacer_size == 0 {
+            return Err(anyhow!("minimum spacer size must be at least 1"))
+        }
+        if self.min_spacer_size > self.max_spacer_size {
+            return Err(anyhow!("minimum spacer size ({}) is larger than the maximum ({})", self.min_spacer_size, self.max_spacer_size))
+        }
+        for (name, dev) in [("repeat", self.max_rep_len_dev), ("spacer", self.max_spacer_len_dev)] {
+            if !(0.0..=1.0).contains(&dev) {
+                return Err(anyhow!("maximum {name} length deviation must be between 0 and 1"))
+            }
+        }
+        if self.max_indels >= self.min_rep_size || self.max_mismatches >= self.min_rep_size {
+            return Err(anyhow!("maximum mismatches and indels must be less than the minimum repeat size"))
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_params_are_valid() {
+        assert!(DetectionParams::default().validate().is_ok());
+    }
+
+    #[test]
+    fn inconsistent_params_are_err() {
+        let params = DetectionParams { min_rep_size: 80, ..Default::default() };
+        assert!(params.validate().is_err());
+        let params = DetectionParams { kmer_sizes: vec![11, 25], ..Default::default() };
+        assert!(params.validate().is_err());
+        let params = DetectionParams { kmer_sizes: Vec::new(), ..Default::default() };
+        assert!(params.validate().is_err());
+        let params = DetectionParams { max_spacer_len_dev: 1.5, ..Default::default() };
+        assert!(params.validate().is_err());
+    }
+}
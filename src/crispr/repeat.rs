@@ -0,0 +1,184 @@
+use std::fmt;
+
+use crate::align::{AlignOp, FitAlignment};
+use crate::fasta::Seq;
+use crate::slice::SeqSlice;
+
+
+/// A difference between one repeat copy and the array's consensus repeat.
+/// `pos` is the 0-based position in the consensus.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Variant {
+    Mismatch { pos: usize, expected: u8, found: u8 },
+    /// Bases found in the copy before consensus position `pos`
+    Insertion { pos: usize, bases: Vec<u8> },
+    /// `len` consensus bases starting at `pos` missing from the copy
+    Deletion { pos: usize, len: usize },
+}
+
+// Displayed with 1-based positions, e.g. 5A>G, 12insTT, 30del1
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Mismatch { pos, expected, found } => write!(f, "{}{}>{}", pos + 1, *expected as char, *found as char),
+            Self::Insertion { pos, bases } => write!(f, "{}ins{}", pos + 1, String::from_utf8_lossy(bases)),
+            Self::Deletion { pos, len } => write!(f, "{}del{}", pos + 1, len),
+        }
+    }
+}
+
+/// One repeat copy in an array
+#[derive(Debug, Clone, PartialEq)]
+pub struct Repeat {
+    seq: Seq,
+    start: usize,
+    variants: Vec<Variant>,
+    degenerate: bool,
+}
+
+// constructors
+impl Repeat {
+    /// A repeat copy identical to the consensus
+    pub fn exact(seq: Seq, start: usize) -> Self {
+        Self {
+            seq,
+            start,
+            variants: Vec::new(),
+            degenerate: false,
+        }
+    }
+
+    /// Build a repeat from the alignment of `consensus` to a region of `source`
+    pub fn from_alignment<'a>(source_seq: impl Into<SeqSlice<'a>>, consensus: &[u8], source: &[u8], aln: &FitAlignment, offset: usize) -> anyhow::Result<Self> {
+        let start = offset + aln.start;
+        let seq = source_seq.into().slice(start, offset + aln.end)?.to_seq();
+        Ok(Self {
+            seq,
+            start,
+            variants: variants_from_ops(consensus, &source[start ..], &aln.ops),
+            degenerate: false,
+        })
+    }
+}
+
+// methods
+impl Repeat {
+    pub fn seq(&self) -> &Seq {
+        &self.seq
+    }
+
+    /// 0-based start in the source sequence
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// 0-based, exclusive end in the source sequence
+    pub fn end(&self) -> usize {
+        self.start + self.seq.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.seq.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.seq.is_empty()
+    }
+
+    pub fn variants(&self) -> &[Variant] {
+        &self.variants
+    }
+
+    pub fn is_exact(&self) -> bool {
+        self.variants.is_empty()
+    }
+
+    /// A terminal copy accepted under a relaxed identity threshold
+    pub fn is_degenerate(&self) -> bool {
+        self.degenerate
+    }
+
+    pub(crate) fn set_degenerate(&mut self) {
+        self.degenerate = true;
+    }
+
+    pub(crate) fn set_variants(&mut self, variants: Vec<Variant>) {
+        self.variants = variants;
+    }
+
+    pub(crate) fn set_start(&mut self, start: usize) {
+        self.start = start;
+    }
+
+    /// The same copy read from the other strand. The start is unchanged as
+    /// coordinates are always on the forward strand
+    pub(crate) fn reverse_complement(&self) -> Self {
+        Self {
+            seq: self.seq.rev_comp(),
+            start: self.start,
+            variants: Vec::new(),
+            degenerate: self.degenerate,
+        }
+    }
+}
+
+impl fmt::Display for Repeat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.seq.fmt(f)
+    }
+}
+
+// Walk an alignment of the consensus to a repeat copy and collect the differences
+pub(crate) fn variants_from_ops(consensus: &[u8], copy: &[u8], ops: &[AlignOp]) -> Vec<Variant> {
+    let mut variants = Vec::new();
+    let mut c = 0usize; // position in consensus
+    let mut r = 0usize; // position in copy
+    for op in ops {
+        match op {
+            AlignOp::Match => {
+                c += 1;
+                r += 1;
+            },
+            AlignOp::Mismatch => {
+                variants.push(Variant::Mismatch { pos: c, expected: consensus[c], found: copy[r] });
+                c += 1;
+                r += 1;
+            },
+            AlignOp::Insertion => {
+                match variants.last_mut() {
+                    Some(Variant::Insertion { pos, bases }) if *pos == c => bases.push(copy[r]),
+                    _ => variants.push(Variant::Insertion { pos: c, bases: vec![copy[r]] }),
+                }
+                r += 1;
+            },
+            AlignOp::Deletion => {
+                match variants.last_mut() {
+                    Some(Variant::Deletion { pos, len }) if *pos + *len == c => *len += 1,
+                    _ => variants.push(Variant::Deletion { pos: c, len: 1 }),
+                }
+                c += 1;
+            },
+        }
+    }
+    variants
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::align::fit;
+
+    #[test]
+    fn variants_from_alignment() {
+        let consensus = b"GATTACAGATTACA";
+        let source = b"CCCGATTACTGATACACCC";
+        let source_seq = Seq::from_dna(String::from_utf8(source.to_vec()).unwrap()).unwrap();
+        let aln = fit(consensus, source);
+        let result = Repeat::from_alignment(&source_seq, consensus, source, &aln, 0).unwrap();
+        assert_eq!(result.start(), 3);
+        assert_eq!(result.to_string(), "GATTACTGATACA");
+        let variants: Vec<String> = result.variants().iter().map(|v| v.to_string()).collect();
+        assert_eq!(variants, vec!["7A>T", "10del1"]);
+    }
+}
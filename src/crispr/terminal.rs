@@ -0,0 +1,109 @@
+use crate::align::fit;
+use crate::slice::SeqSlice;
+
+use super::{CRISPRArray, DetectionParams};
+use super::repeat::Repeat;
+
+// Minimum identity to the consensus for a degenerate terminal repeat
+const MIN_TERMINAL_IDENTITY: f32 = 0.7;
+// Extra bases either side of the expected position to search, as a proportion of the period
+const PERIOD_SLACK: f32 = 0.1;
+
+
+/// Look one period beyond each end of an array for a degenerate copy of the
+/// consensus repeat that is too divergent to have been found by seeding.
+/// Copies with at least `MIN_TERMINAL_IDENTITY` to the consensus are added
+/// to the array and flagged as degenerate.
+///
+/// The array must be on the strand it was found on, with repeats in ascending order.
+pub fn add_degenerate_terminal_repeats(array: CRISPRArray, source_seq: SeqSlice, source: &[u8], params: &DetectionParams) -> CRISPRArray {
+    let consensus = array.consensus.to_bytes();
+    let mut spacer_lens: Vec<usize> = array.spacers.iter().map(|s| s.len()).collect();
+    spacer_lens.sort();
+    let spacer_len = spacer_lens[spacer_lens.len() / 2];
+    let slack = ((spacer_len + consensus.len()) as f32 * PERIOD_SLACK).ceil() as usize;
+
+    let first = array.repeats.first().expect("Arrays always have repeats");
+    let last = array.repeats.last().expect("Arrays always have repeats");
+
+    // Region before the first repeat that would leave a spacer of an acceptable length
+    let before_start = first.start().saturating_sub(spacer_len + consensus.len() + slack);
+    let before_end = first.start().saturating_sub(params.min_spacer_size);
+    let before = find_degenerate_copy(source_seq, source, &consensus, before_start, before_end)
+        .filter(|r| (first.start() - r.end()) <= params.max_spacer_size);
+
+    let after_start = last.end() + params.min_spacer_size;
+    let after_end = (last.end() + spacer_len + consensus.len() + slack).min(source.len());
+    let after = find_degenerate_copy(source_seq, source, &consensus, after_start, after_end)
+        .filter(|r| (r.start() - last.end()) <= params.max_spacer_size);
+
+    if before.is_none() && after.is_none() {
+        return array
+    }
+    let mut repeats: Vec<Repeat> = Vec::with_capacity(array.repeats.len() + 2);
+    repeats.extend(before);
+    repeats.extend(array.repeats.iter().cloned());
+    repeats.extend(after);
+    CRISPRArray::from_repeats(source_seq, &array.source_name, repeats)
+        .unwrap_or(array)
+}
+
+fn find_degenerate_copy(source_seq: SeqSlice, source: &[u8], consensus: &[u8], start: usize, end: usize) -> Option<Repeat> {
+    if end <= start || end - start < consensus.len() / 2 {
+        return None
+    }
+    let aln = fit(consensus, &source[start .. end]);
+    let identity = 1.0 - aln.edits() as f32 / consensus.len() as f32;
+    if identity < MIN_TERMINAL_IDENTITY {
+        return None
+    }
+    let mut rep = Repeat::from_alignment(source_seq, consensus, source, &aln, start).ok()?;
+    rep.set_degenerate();
+    Some(rep)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fasta::Seq;
+    use super::super::tests::{random_dna, TEST_REPEAT};
+
+    // Mutate every fifth base of the repeat
+    fn degenerate(repeat: &str) -> String {
+        repeat.bytes().enumerate()
+            .map(|(i, b)| match (i % 5, b) {
+                (2, b'A') => 'C',
+                (2, _) => 'A',
+                (_, b) => b as char,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn adds_degenerate_trailing_repeat() {
+        let contig = [
+            &random_dna(100, 1), TEST_REPEAT, &random_dna(30, 2), TEST_REPEAT, &random_dna(30, 3),
+            TEST_REPEAT, &random_dna(30, 4), &degenerate(TEST_REPEAT), &random_dna(100, 5)
+        ].concat();
+        let seq = Seq::from_dna(contig.clone()).unwrap();
+        let array = CRISPRArray::from_repeat_starts(&seq, "contig", &[100, 166, 232], 36).unwrap();
+        let result = add_degenerate_terminal_repeats(array, seq.as_slice(), contig.as_bytes(), &DetectionParams::default());
+        assert_eq!(result.repeats().len(), 4);
+        assert_eq!(result.repeats()[3].start(), 298);
+        assert!(result.repeats()[3].is_degenerate());
+        assert!(result.repeats()[..3].iter().all(|r| !r.is_degenerate()));
+    }
+
+    #[test]
+    fn ignores_unrelated_flank() {
+        let contig = [
+            &random_dna(100, 1), TEST_REPEAT, &random_dna(30, 2), TEST_REPEAT, &random_dna(30, 3),
+            TEST_REPEAT, &random_dna(100, 5)
+        ].concat();
+        let seq = Seq::from_dna(contig.clone()).unwrap();
+        let array = CRISPRArray::from_repeat_starts(&seq, "contig", &[100, 166, 232], 36).unwrap();
+        let result = add_degenerate_terminal_repeats(array, seq.as_slice(), contig.as_bytes(), &DetectionParams::default());
+        assert_eq!(result.repeats().len(), 3);
+    }
+}
@@ -0,0 +1,213 @@
+use std::fmt;
+
+use crate::align::identity;
+use crate::fasta::Seq;
+
+use super::{CRISPRArray, DetectionParams};
+
+// Spacers more similar than this to the repeat or each other are unlikely to be real
+const MAX_SPACER_REPEAT_IDENTITY: f32 = 0.75;
+const MAX_SPACER_SPACER_IDENTITY: f32 = 0.75;
+
+/// Why a candidate array failed validation or was filtered out as a false positive
+#[derive(Debug, Clone, PartialEq)]
+pub enum RejectReason {
+    TooFewRepeats(usize),
+    RepeatLength(usize),
+    RepeatLengthDeviation(f32),
+    SpacerLength(usize),
+    SpacerLengthDeviation(f32),
+    SpacerSimilarToRepeat(f32),
+    SimilarSpacers(f32),
+    Microsatellite(usize),
+    LowComplexity(f32),
+    TandemRepeat(f32),
+    LowConfidence(f64),
+}
+
+impl fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::TooFewRepeats(n) => write!(f, "too few repeats ({n})"),
+            Self::RepeatLength(len) => write!(f, "repeat length {len} out of range"),
+            Self::RepeatLengthDeviation(dev) => write!(f, "repeat length deviation {dev:.2} too high"),
+            Self::SpacerLength(len) => write!(f, "spacer length {len} out of range"),
+            Self::SpacerLengthDeviation(dev) => write!(f, "spacer length deviation {dev:.2} too high"),
+            Self::SpacerSimilarToRepeat(id) => write!(f, "spacer {id:.2} identical to repeat"),
+            Self::SimilarSpacers(id) => write!(f, "spacers {id:.2} identical to each other"),
+            Self::Microsatellite(unit) => write!(f, "microsatellite with {unit} bp unit"),
+            Self::LowComplexity(c) => write!(f, "low complexity ({c:.2})"),
+            Self::TandemRepeat(score) => write!(f, "tandem repeat (score {score:.2})"),
+            Self::LowConfidence(score) => write!(f, "confidence {score:.2} below threshold"),
+        }
+    }
+}
+
+/// A candidate array that failed validation, kept for debug output
+#[derive(Debug)]
+pub struct Rejection {
+    array: CRISPRArray,
+    reason: RejectReason,
+}
+
+impl Rejection {
+    pub fn new(array: CRISPRArray, reason: RejectReason) -> Self {
+        Self { array, reason }
+    }
+
+    pub fn array(&self) -> &CRISPRArray {
+        &self.array
+    }
+
+    pub fn reason(&self) -> &RejectReason {
+        &self.reason
+    }
+
+    pub(crate) fn into_parts(self) -> (CRISPRArray, RejectReason) {
+        (self.array, self.reason)
+    }
+
+    pub fn summary_header() -> String {
+        ["array_id", "contig", "start", "end", "strand", "repeats", "consensus_repeat", "reason"].join("\t")
+    }
+
+    pub fn to_summary(&self) -> String {
+        let [start, end] = self.array.location();
+        format!("{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            self.array.id(),
+            self.array.contig(),
+            start,
+            end,
+            self.array.strand(),
+            self.array.repeats().len(),
+            self.array.consensus(),
+            self.reason,
+        )
+    }
+}
+
+/// Check a candidate array against the repeat and spacer rules in `params`.
+/// The first rule the array breaks is returned as the reason for rejection.
+pub fn validate(array: &CRISPRArray, params: &DetectionParams) -> Result<(), RejectReason> {
+    if array.repeats.len() < params.min_reps {
+        return Err(RejectReason::TooFewRepeats(array.repeats.len()))
+    }
+
+    let rep_lens: Vec<usize> = array.repeats.iter().map(|r| r.len()).collect();
+    if let Some(len) = rep_lens.iter().find(|l| !(params.min_rep_size ..= params.max_rep_size).contains(l)) {
+        return Err(RejectReason::RepeatLength(*len))
+    }
+    let dev = length_deviation(&rep_lens);
+    if dev > params.max_rep_len_dev {
+        return Err(RejectReason::RepeatLengthDeviation(dev))
+    }
+
+    // Spacers spanning assembly gaps have no meaningful length or sequence.
+    // The array is flagged as possibly incomplete instead
+    let gapless: Vec<&Seq> = array.spacers.iter().filter(|s| !s.contains_n()).collect();
+    let spacer_lens: Vec<usize> = gapless.iter().map(|s| s.len()).collect();
+    if let Some(len) = spacer_lens.iter().find(|l| !(params.min_spacer_size ..= params.max_spacer_size).contains(l)) {
+        return Err(RejectReason::SpacerLength(*len))
+    }
+    let dev = length_deviation(&spacer_lens);
+    if dev > params.max_spacer_len_dev {
+        return Err(RejectReason::SpacerLengthDeviation(dev))
+    }
+
+    let spacers: Vec<Vec<u8>> = gapless.iter().map(|s| s.to_bytes()).collect();
+    let id = max_spacer_repeat_identity(&spacers, &array.consensus.to_bytes());
+    if id > MAX_SPACER_REPEAT_IDENTITY {
+        return Err(RejectReason::SpacerSimilarToRepeat(id))
+    }
+    let id = max_spacer_spacer_identity(&spacers);
+    if id > MAX_SPACER_SPACER_IDENTITY {
+        return Err(RejectReason::SimilarSpacers(id))
+    }
+
+    Ok(())
+}
+
+/// Largest proportional difference between any length and the median length
+pub(crate) fn length_deviation(lens: &[usize]) -> f32 {
+    if lens.is_empty() { return 0.0 }
+    let mut sorted = lens.to_vec();
+    sorted.sort();
+    let median = sorted[sorted.len() / 2] as f32;
+    sorted.iter()
+        .map(|l| (*l as f32 - median).abs() / median)
+        .fold(0.0, f32::max)
+}
+
+pub(crate) fn max_spacer_repeat_identity(spacers: &[Vec<u8>], repeat: &[u8]) -> f32 {
+    spacers.iter()
+        .map(|s| identity(s, repeat))
+        .fold(0.0, f32::max)
+}
+
+pub(crate) fn max_spacer_spacer_identity(spacers: &[Vec<u8>]) -> f32 {
+    let mut max_id = 0.0f32;
+    for (i, a) in spacers.iter().enumerate() {
+        for b in &spacers[i + 1 ..] {
+            max_id = max_id.max(identity(a, b));
+        }
+    }
+    max_id
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{ConservationProfile, EvidenceLevel, Repeat, Strand};
+    use super::super::tests::random_dna;
+
+    fn array_from_parts(repeat: &str, spacers: &[String]) -> CRISPRArray {
+        let rep = Seq::from_dna(repeat.to_string()).unwrap();
+        CRISPRArray {
+            repeats: vec![Repeat::exact(rep.clone(), 0); spacers.len() + 1],
+            spacers: spacers.iter().map(|s| Seq::from_dna(s.clone()).unwrap()).collect(),
+            source_name: "test".to_string(),
+            location: [0, 0],
+            consensus: rep,
+            conservation: ConservationProfile::default(),
+            strand: Strand::Forward,
+            orientation: None,
+            flags: Vec::new(),
+            confidence: 0.0,
+            evidence: EvidenceLevel::One,
+            supporting_k: Vec::new(),
+            library_match: None,
+            leader: None,
+            interruptions: Vec::new(),
+            boundary_shift: [0, 0],
+            merged_calls: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn validate_accepts_good_array() {
+        let spacers: Vec<String> = (0..4).map(|i| random_dna(32, i)).collect();
+        let array = array_from_parts("GTTTTAGAGCTATGCTGTTTTGAATGGTCCCAAAAC", &spacers);
+        assert_eq!(validate(&array, &DetectionParams::default()), Ok(()));
+    }
+
+    #[test]
+    fn validate_rejects_uneven_spacers() {
+        let spacers = vec![random_dna(32, 1), random_dna(32, 2), random_dna(45, 3)];
+        let array = array_from_parts("GTTTTAGAGCTATGCTGTTTTGAATGGTCCCAAAAC", &spacers);
+        assert!(matches!(validate(&array, &DetectionParams::default()), Err(RejectReason::SpacerLengthDeviation(_))));
+    }
+
+    #[test]
+    fn validate_rejects_repeated_spacers() {
+        let spacers = vec![random_dna(32, 1), random_dna(32, 1), random_dna(32, 5)];
+        let array = array_from_parts("GTTTTAGAGCTATGCTGTTTTGAATGGTCCCAAAAC", &spacers);
+        assert!(matches!(validate(&array, &DetectionParams::default()), Err(RejectReason::SimilarSpacers(_))));
+    }
+
+    #[test]
+    fn validate_rejects_too_few_repeats() {
+        let array = array_from_parts("GTTTTAGAGCTATGCTGTTTTGAATGGTCCCAAAAC", &[random_dna(32, 1)]);
+        assert_eq!(validate(&array, &DetectionParams::default()), Err(RejectReason::TooFewRepeats(2)));
+    }
+}
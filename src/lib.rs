@@ -4,6 +4,7 @@
 #![allow(unreachable_code)]
 
 use std::error::Error;
+use std::fs;
 
 use anyhow::{anyhow, Result, Context};
 
@@ -11,19 +12,99 @@ use anyhow::{anyhow, Result, Context};
 pub mod seq;
 pub mod crispr;
 pub mod cli;
-pub use seq::{kmer, fasta};
+pub use seq::{kmer, fasta, align, slice, translate};
 
 mod clusters;
 
 pub fn run(args: cli::Opts) -> Result<(), Box<dyn Error>> {
-    let contigs = fasta::Fasta::from_file(args.assembly())
+    let contigs = fasta::Fasta::from_file_with_policy(args.assembly(), args.ambiguity_policy())
         .unwrap_or_else(|error| {
             panic!("Issue loading assembly: {error:?}");
         }
     );
+    let params = args.detection_params();
+    params.validate()?;
+    let library = match args.repeat_library() {
+        Some(file) => Some(crispr::RepeatLibrary::from_file(file)?),
+        None if args.mode() != cli::Mode::Denovo => return Err(anyhow!("--repeat-library is needed for {:?} mode", args.mode()).into()),
+        None => None,
+    };
+    let mut arrays = Vec::<crispr::CRISPRArray>::new();
+    let mut rejected = Vec::<crispr::Rejection>::new();
     for (name, seq) in contigs.iter() {
-        let crs = crispr::find_crisprs(seq, name, 11usize);
+        let circular = args.circular() || fasta::header_is_circular(name);
+        let (mut found, failed) = match (args.mode(), circular) {
+            (cli::Mode::Library, _) => (Vec::new(), Vec::new()),
+            (_, true) => crispr::find_crisprs_circular_with_rejections(seq, name, &params)?,
+            (_, false) => crispr::find_crisprs_with_rejections(seq, name, &params)?,
+        };
+        if let Some(library) = &library {
+            crispr::library::annotate(&mut found, library);
+            if args.mode() != cli::Mode::Denovo {
+                let mut library_calls = crispr::library::find_library_arrays(seq, name, library, &params);
+                if !circular {
+                    crispr::library::flag_truncated(&mut library_calls, seq.len());
+                }
+                found = crispr::library::merge_calls(found, library_calls);
+            }
+        }
+        if args.report_masked() {
+            crispr::flags::flag_masked(&mut found, seq);
+        }
+        crispr::leader::add_leaders(&mut found, seq, args.leader_length());
+        for array in found {
+            match array.confidence() >= args.min_score() {
+                true => arrays.push(array),
+                false => {
+                    let reason = crispr::RejectReason::LowConfidence(array.confidence());
+                    rejected.push(crispr::Rejection::new(array, reason));
+                },
+            }
+        }
+        rejected.extend(failed);
     };
+    crispr::leader::compare_leaders(&mut arrays);
+    arrays.sort_by(|a, b| a.source_name().cmp(b.source_name())
+        .then(a.location().cmp(&b.location()))
+    );
+
+    write_outputs(&arrays, args.outprefix())?;
+    if args.debug() {
+        rejected.sort_by(|a, b| a.array().source_name().cmp(b.array().source_name())
+            .then(a.array().location().cmp(&b.array().location()))
+        );
+        write_rejected(&rejected, args.outprefix())?;
+    }
+
+    Ok(())
+}
+
+fn write_outputs(arrays: &[crispr::CRISPRArray], outprefix: &str) -> Result<()> {
+    let fasta_lines: Vec<String> = arrays.iter()
+        .map(|a| a.to_fasta(&a.id()) + "\n")
+        .collect();
+    fs::write(format!("{outprefix}.fasta"), fasta_lines.join(""))
+        .context("Could not write array fasta file")?;
+
+    let mut summary_lines = vec![crispr::CRISPRArray::summary_header()];
+    summary_lines.extend(arrays.iter().map(|a| a.to_summary()));
+    fs::write(format!("{outprefix}.tsv"), summary_lines.join("\n") + "\n")
+        .context("Could not write array summary file")?;
+
+    let leader_lines: Vec<String> = arrays.iter()
+        .filter_map(|a| a.leader().map(|l| format!(">{} leader={}-{}\n{}\n", a.id(), l.location()[0], l.location()[1], l.seq())))
+        .collect();
+    fs::write(format!("{outprefix}_leaders.fasta"), leader_lines.join(""))
+        .context("Could not write leader fasta file")?;
+
+    Ok(())
+}
+
+fn write_rejected(rejected: &[crispr::Rejection], outprefix: &str) -> Result<()> {
+    let mut lines = vec![crispr::Rejection::summary_header()];
+    lines.extend(rejected.iter().map(|r| r.to_summary()));
+    fs::write(format!("{outprefix}_rejected.tsv"), lines.join("\n") + "\n")
+        .context("Could not write rejected candidates file")?;
 
     Ok(())
 }
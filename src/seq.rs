@@ -1,2 +1,5 @@
 pub mod kmer;
 pub mod fasta;
+pub mod align;
+pub mod slice;
+pub mod translate;
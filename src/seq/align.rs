@@ -0,0 +1,184 @@
+/// Whether two bases can be the same base. IUPAC ambiguity codes are
+/// compatible with every base they stand for, but N is compatible with
+/// nothing so that gaps in an assembly never count as matches.
+pub fn compatible(a: u8, b: u8) -> bool {
+    a == b && a != b'N' || iupac_mask(a) & iupac_mask(b) != 0
+}
+
+// Bases a code stands for, one bit each for A, C, G and T
+pub(crate) fn iupac_mask(base: u8) -> u8 {
+    match base.to_ascii_uppercase() {
+        b'A' => 0b0001,
+        b'C' => 0b0010,
+        b'G' => 0b0100,
+        b'T' => 0b1000,
+        b'R' => 0b0101,
+        b'Y' => 0b1010,
+        b'S' => 0b0110,
+        b'W' => 0b1001,
+        b'K' => 0b1100,
+        b'M' => 0b0011,
+        b'B' => 0b1110,
+        b'D' => 0b1101,
+        b'H' => 0b1011,
+        b'V' => 0b0111,
+        _ => 0,
+    }
+}
+
+/// Levenshtein distance between two sequences
+pub fn edit_distance(a: &[u8], b: &[u8]) -> usize {
+    if a.is_empty() { return b.len() }
+    if b.is_empty() { return a.len() }
+
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0usize; b.len() + 1];
+    for (i, base_a) in a.iter().enumerate() {
+        curr[0] = i + 1;
+        for (j, base_b) in b.iter().enumerate() {
+            let sub = prev[j] + usize::from(!compatible(*base_a, *base_b));
+            curr[j + 1] = sub.min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    prev[b.len()]
+}
+
+/// Proportion identity between two sequences based on their edit distance,
+/// relative to the length of the longer sequence
+pub fn identity(a: &[u8], b: &[u8]) -> f32 {
+    let longest = a.len().max(b.len());
+    if longest == 0 { return 1.0 }
+    1.0 - edit_distance(a, b) as f32 / longest as f32
+}
+
+/// A single column of a pairwise alignment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlignOp {
+    Match,
+    Mismatch,
+    /// Base present in the target but not the query
+    Insertion,
+    /// Base present in the query but not the target
+    Deletion,
+}
+
+/// Alignment of a whole query to part of a target. `start` and `end` are the
+/// 0-based, end-exclusive coordinates of the aligned region in the target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FitAlignment {
+    pub start: usize,
+    pub end: usize,
+    pub ops: Vec<AlignOp>,
+}
+
+impl FitAlignment {
+    pub fn mismatches(&self) -> usize {
+        self.ops.iter().filter(|op| **op == AlignOp::Mismatch).count()
+    }
+
+    pub fn indels(&self) -> usize {
+        self.ops.iter().filter(|op| matches!(op, AlignOp::Insertion | AlignOp::Deletion)).count()
+    }
+
+    pub fn edits(&self) -> usize {
+        self.mismatches() + self.indels()
+    }
+}
+
+/// Find the best placement of all of `query` within `target` by edit distance
+/// (a "fitting" alignment: gaps before and after the query in the target are free).
+/// Ties are broken in favour of fewer indels.
+pub fn fit(query: &[u8], target: &[u8]) -> FitAlignment {
+    let m = query.len();
+    let n = target.len();
+    // (edits, indels) so equal-cost alignments prefer substitutions
+    let mut dp = vec![vec![(0usize, 0usize); n + 1]; m + 1];
+    for (i, row) in dp.iter_mut().enumerate().skip(1) {
+        row[0] = (i, i);
+    }
+    for i in 1..=m {
+        for j in 1..=n {
+            let diag = dp[i - 1][j - 1];
+            let diag = (diag.0 + usize::from(!compatible(query[i - 1], target[j - 1])), diag.1);
+            let up = (dp[i - 1][j].0 + 1, dp[i - 1][j].1 + 1);
+            let left = (dp[i][j - 1].0 + 1, dp[i][j - 1].1 + 1);
+            dp[i][j] = diag.min(up).min(left);
+        }
+    }
+
+    let mut j = (0..=n).min_by_key(|j| dp[m][*j])
+        .expect("Range always has at least one element");
+    let end = j;
+    let mut i = m;
+    let mut ops = Vec::with_capacity(m);
+    while i > 0 {
+        let here = dp[i][j];
+        if j > 0 {
+            let diag = dp[i - 1][j - 1];
+            let is_match = compatible(query[i - 1], target[j - 1]);
+            if here == (diag.0 + usize::from(!is_match), diag.1) {
+                ops.push(if is_match { AlignOp::Match } else { AlignOp::Mismatch });
+                i -= 1;
+                j -= 1;
+                continue
+            }
+            let left = dp[i][j - 1];
+            if here == (left.0 + 1, left.1 + 1) {
+                ops.push(AlignOp::Insertion);
+                j -= 1;
+                continue
+            }
+        }
+        ops.push(AlignOp::Deletion);
+        i -= 1;
+    }
+    ops.reverse();
+    FitAlignment { start: j, end, ops }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edit_distance_works() {
+        assert_eq!(edit_distance(b"ATCG", b"ATCG"), 0);
+        assert_eq!(edit_distance(b"ATCG", b"ATGG"), 1);
+        assert_eq!(edit_distance(b"ATCG", b"ACG"), 1);
+        assert_eq!(edit_distance(b"", b"ACG"), 3);
+    }
+
+    #[test]
+    fn ambiguity_codes_are_compatible() {
+        assert!(compatible(b'A', b'R'));
+        assert!(compatible(b'Y', b'S'));
+        assert!(!compatible(b'A', b'Y'));
+        assert!(!compatible(b'N', b'N'));
+        assert_eq!(edit_distance(b"ATCG", b"WYSK"), 0);
+    }
+
+    #[test]
+    fn identity_works() {
+        assert_eq!(identity(b"ATCG", b"ATCG"), 1.0);
+        assert_eq!(identity(b"ATCG", b"ATGG"), 0.75);
+    }
+
+    #[test]
+    fn fit_finds_query_in_target() {
+        let result = fit(b"GATTACA", b"CCCCGATTACACCCC");
+        assert_eq!((result.start, result.end, result.edits()), (4, 11, 0));
+    }
+
+    #[test]
+    fn fit_reports_indels() {
+        let result = fit(b"GATTACA", b"CCCCGATACACCCC");
+        assert_eq!((result.start, result.end), (4, 10));
+        assert_eq!(result.indels(), 1);
+        assert_eq!(result.mismatches(), 0);
+
+        let result = fit(b"GATTACA", b"CCCCGATTTACACCCC");
+        assert_eq!((result.start, result.end), (4, 12));
+        assert_eq!(result.ops.iter().filter(|op| **op == AlignOp::Insertion).count(), 1);
+    }
+}
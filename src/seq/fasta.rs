@@ -1,11 +1,15 @@
 use std::{any, collections::HashMap, collections::HashSet};
 use std::fs;
+use std::hash::{Hash, Hasher};
 
 use anyhow::{anyhow, Context, Result, Error};
 
+use crate::seq::slice::SeqSlice;
+
 // Private constants
 
 const DNA_BASES: &str = "ATCGN";
+const AMBIGUITY_CODES: &str = "RYSWKMBDHV";
 
 #[derive(Debug)]
 pub struct Fasta {
@@ -15,15 +19,24 @@ pub struct Fasta {
 // Constructors
 impl Fasta {
     pub fn from_file(file: &str) -> Result<Self, Error> {
+        Self::from_file_with_policy(file, AmbiguityPolicy::Keep)
+    }
+
+    /// Read a FASTA file, handling ambiguity codes according to `policy`
+    pub fn from_file_with_policy(file: &str, policy: AmbiguityPolicy) -> Result<Self, Error> {
         let fasta_string = fs::read_to_string(file)
             .context("Could not read Fasta file")?;
-        match Self::from_string(fasta_string) {
+        match Self::from_string_with_policy(fasta_string, policy) {
             Ok(instance) => Ok(instance),
             Err(e) => Err(anyhow!("Issue processing fasta file: {e:?}")),
         }
     }
 
     pub fn from_string(fasta: String) -> Result<Self, Error> {
+        Self::from_string_with_policy(fasta, AmbiguityPolicy::Keep)
+    }
+
+    pub fn from_string_with_policy(fasta: String, policy: AmbiguityPolicy) -> Result<Self, Error> {
         // check input starts with valid characters
         match &fasta.trim().chars().next() {
             Some('>') => (),
@@ -57,10 +70,8 @@ impl Fasta {
 
             let seq = lines.collect::<Vec<&str>>()
                 .join("");
-            let dna = match Seq::from_dna(seq) {
-                Ok(s) => s,
-                Err(e) => return Err(e),
-            };
+            let dna = Seq::from_dna_with_policy(seq, policy)
+                .map_err(|e| anyhow!("{e} in sequence {header}"))?;
             seqs.insert(header, dna);
         }
     Ok(Self{seqs})
@@ -81,67 +92,410 @@ impl Fasta {
     }
 }
 
-#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+/// Whether a header marks its sequence as circular with a `circular=true` tag,
+/// as written by assemblers such as Flye and Unicycler
+pub fn header_is_circular(header: &str) -> bool {
+    header.split_whitespace()
+        .any(|field| field.eq_ignore_ascii_case("circular=true"))
+}
+
+/// What to do with IUPAC ambiguity codes (R, Y, S, W, K, M, B, D, H, V) when reading DNA
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AmbiguityPolicy {
+    /// Keep the code
+    #[default]
+    Keep,
+    /// Replace the code with N
+    ToN,
+    /// Fail, reporting the position of the first code
+    Reject,
+}
+
+/// 2-bit code of an uppercase base (A=0, C=1, G=2, T=3), or `None` for N
+/// and other ambiguity codes
+pub fn base_code(base: u8) -> Option<u8> {
+    match base {
+        b'A' => Some(0),
+        b'C' => Some(1),
+        b'G' => Some(2),
+        b'T' => Some(3),
+        _ => None,
+    }
+}
+
+/// Complement of a base or IUPAC ambiguity code
+pub fn complement(base: u8) -> u8 {
+    match base {
+        b'A' => b'T',
+        b'T' => b'A',
+        b'C' => b'G',
+        b'G' => b'C',
+        b'R' => b'Y',
+        b'Y' => b'R',
+        b'K' => b'M',
+        b'M' => b'K',
+        b'B' => b'V',
+        b'V' => b'B',
+        b'D' => b'H',
+        b'H' => b'D',
+        other => other, // N, S and W are their own complements
+    }
+}
+
+/// DNA sequence packed at two bits per base (A=0, C=1, G=2, T=3), 32 bases to
+/// a word, with the first base in the lowest bits. N and other ambiguity codes
+/// are stored as A in the packed bases. Ns, which come in long runs at
+/// assembly gaps, are kept as runs; other ambiguity codes are rare and are
+/// kept with their position.
+///
+/// Soft-masked (lowercase) bases are read as uppercase, with the masked runs
+/// kept alongside. The mask is annotation, so it doesn't take part in equality
+/// or hashing.
+#[derive(Debug, Clone)]
 pub struct Seq {
-    seq: String
+    packed: Vec<u64>,
+    len: usize,
+    n_runs: Vec<[usize; 2]>,
+    ambiguous: Vec<(usize, u8)>,
+    masked: Vec<[usize; 2]>,
 }
 
+impl PartialEq for Seq {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.packed == other.packed
+            && self.n_runs == other.n_runs && self.ambiguous == other.ambiguous
+    }
+}
+
+impl Eq for Seq {}
+
+impl Hash for Seq {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.packed.hash(state);
+        self.len.hash(state);
+        self.n_runs.hash(state);
+        self.ambiguous.hash(state);
+    }
+}
+
+const BASES_PER_WORD: usize = 32;
+
 // Constructors
 impl Seq {
+    /// Read DNA, keeping any IUPAC ambiguity codes
     pub fn from_dna(bases: String) -> Result<Self> {
+        Self::from_dna_with_policy(bases, AmbiguityPolicy::Keep)
+    }
+
+    pub fn from_dna_with_policy(bases: String, policy: AmbiguityPolicy) -> Result<Self> {
         let bases = match bases.contains("\n") {
-            true => bases.replace('\n', "").to_uppercase(),
-            false => bases.to_uppercase(),
+            true => bases.replace('\n', ""),
+            false => bases,
         };
-        if !bases.chars().all(|b| DNA_BASES.contains(b)) {
-            return Err(anyhow!("non-ATCGN base found in DNA sequence"))
+        let masked = lowercase_runs(bases.as_bytes());
+        // ASCII only, so every byte is one base and lines up with the mask
+        let bases = bases.to_ascii_uppercase();
+        if !bases.bytes().all(|b| DNA_BASES.as_bytes().contains(&b) || AMBIGUITY_CODES.as_bytes().contains(&b)) {
+            return Err(anyhow!("non-IUPAC base found in DNA sequence"))
+        }
+        let mut packed = vec![0u64; bases.len().div_ceil(BASES_PER_WORD)];
+        let mut n_runs = Vec::new();
+        let mut ambiguous = Vec::new();
+        for (i, b) in bases.bytes().enumerate() {
+            let code = match b {
+                b'A' => 0,
+                b'C' => 1,
+                b'G' => 2,
+                b'T' => 3,
+                b'N' => {
+                    extend_runs(&mut n_runs, i);
+                    0
+                },
+                _ => {
+                    match policy {
+                        AmbiguityPolicy::Keep => ambiguous.push((i, b)),
+                        AmbiguityPolicy::ToN => extend_runs(&mut n_runs, i),
+                        AmbiguityPolicy::Reject => return Err(anyhow!("ambiguity code {} found at position {}", b as char, i + 1)),
+                    }
+                    0
+                },
+            };
+            packed[i / BASES_PER_WORD] |= code << (2 * (i % BASES_PER_WORD));
+        }
+        Ok(Self { packed, len: bases.len(), n_runs, ambiguous, masked })
+    }
+
+    // Bases `start..start + len` of `words` packed from the first bit.
+    // Bits past the end are zero
+    fn extract(words: &[u64], start: usize, len: usize) -> Vec<u64> {
+        let first = start / BASES_PER_WORD;
+        let shift = 2 * (start % BASES_PER_WORD);
+        let mut packed: Vec<u64> = (0 .. len.div_ceil(BASES_PER_WORD))
+            .map(|w| {
+                let low = words.get(first + w).map_or(0, |word| word >> shift);
+                let high = match shift {
+                    0 => 0,
+                    _ => words.get(first + w + 1).map_or(0, |word| word << (64 - shift)),
+                };
+                low | high
+            })
+            .collect();
+        if let Some(last) = packed.last_mut() {
+            let used = len % BASES_PER_WORD;
+            if used > 0 {
+                *last &= (1u64 << (2 * used)) - 1;
+            }
         }
-        Ok(Self {seq: bases})
+        packed
     }
 }
 
 impl Seq {
     pub fn len(&self) -> usize {
-        self.seq.len()
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn contains_n(&self) -> bool {
+        !self.n_runs.is_empty()
+    }
+
+    // Positions of ambiguity codes other than N in `start..stop`
+    fn ambiguous_in(&self, start: usize, stop: usize) -> &[(usize, u8)] {
+        let first = self.ambiguous.partition_point(|(p, _)| *p < start);
+        let last = self.ambiguous.partition_point(|(p, _)| *p < stop);
+        &self.ambiguous[first .. last.max(first)]
+    }
+
+    /// Whether any of the bases in `start..stop` is N
+    pub fn contains_n_in(&self, start: usize, stop: usize) -> bool {
+        !runs_in(&self.n_runs, start, stop).is_empty()
+    }
+
+    /// Whether any of the bases in `start..stop` is N or another ambiguity code
+    pub fn contains_ambiguous_in(&self, start: usize, stop: usize) -> bool {
+        self.contains_n_in(start, stop) || !self.ambiguous_in(start, stop).is_empty()
+    }
+
+    /// Borrowed view of the whole sequence
+    pub fn as_slice(&self) -> SeqSlice<'_> {
+        SeqSlice::new(self, 0, self.len).expect("Whole sequence is a valid view")
+    }
+
+    /// Borrowed view of `start..stop`, without copying
+    pub fn slice(&self, start: usize, stop: usize) -> Result<SeqSlice<'_>> {
+        SeqSlice::new(self, start, stop)
+    }
+
+    // 2-bit code of the base at `index`, or None for N and other ambiguity codes
+    pub(crate) fn code_at(&self, index: usize) -> Option<u8> {
+        match self.ambiguous_at(index) {
+            Some(_) => None,
+            None => Some(self.code(index)),
+        }
+    }
+
+    // Uppercase ASCII base or ambiguity code at `index`
+    pub(crate) fn base_at(&self, index: usize) -> u8 {
+        self.ambiguous_at(index).unwrap_or(b"ACGT"[self.code(index) as usize])
+    }
+
+    fn code(&self, index: usize) -> u8 {
+        ((self.packed[index / BASES_PER_WORD] >> (2 * (index % BASES_PER_WORD))) & 0b11) as u8
+    }
+
+    fn ambiguous_at(&self, index: usize) -> Option<u8> {
+        if in_runs(&self.n_runs, index) {
+            return Some(b'N')
+        }
+        self.ambiguous.binary_search_by_key(&index, |(p, _)| *p).ok()
+            .map(|i| self.ambiguous[i].1)
+    }
+
+    /// Uppercase ASCII bases, for code that needs to compare positions directly
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.bytes_in(0, self.len)
+    }
+
+    // Uppercase ASCII bases of `start..stop`
+    pub(crate) fn bytes_in(&self, start: usize, stop: usize) -> Vec<u8> {
+        let mut bytes: Vec<u8> = (start .. stop)
+            .map(|i| b"ACGT"[self.code(i) as usize])
+            .collect();
+        for [s, e] in runs_in(&self.n_runs, start, stop) {
+            bytes[s - start .. e - start].fill(b'N');
+        }
+        for (p, b) in self.ambiguous_in(start, stop) {
+            bytes[p - start] = *b;
+        }
+        bytes
     }
 
     pub fn get_range(&self, start: usize, stop: usize) -> Result<Self> {
-        if start >= self.seq.len() {return Err(anyhow!("start index must be less than sequence length"))}
-        if stop > self.seq.len() {return Err(anyhow!("stop index must be less than or equal to sequence length"))}
-        let slice = self.seq[start..stop].to_string();
-        let new_instance = Self::from_dna(slice)
-            .expect("A Slice of an existing Seq should not throw any errors");
-        Ok(new_instance)
+        if start >= self.len {return Err(anyhow!("start index must be less than sequence length"))}
+        if stop > self.len {return Err(anyhow!("stop index must be less than or equal to sequence length"))}
+        let len = stop.saturating_sub(start);
+        let ambiguous = self.ambiguous_in(start, stop).iter()
+            .map(|(p, b)| (p - start, *b))
+            .collect();
+        let shift = |runs: Vec<[usize; 2]>| runs.into_iter()
+            .map(|[s, e]| [s - start, e - start])
+            .collect();
+        let n_runs = shift(runs_in(&self.n_runs, start, stop));
+        let masked = shift(self.masked_regions_in(start, stop));
+        Ok(Self { packed: Self::extract(&self.packed, start, len), len, n_runs, ambiguous, masked })
+    }
+
+    /// This sequence followed by `other`, joined word by word
+    pub fn concat(&self, other: &Seq) -> Seq {
+        let len = self.len + other.len;
+        let mut packed = self.packed.clone();
+        packed.resize(len.div_ceil(BASES_PER_WORD), 0);
+        let first = self.len / BASES_PER_WORD;
+        let shift = 2 * (self.len % BASES_PER_WORD);
+        // Bits past the end of a sequence are zero, so words can be ORed in
+        for (i, word) in other.packed.iter().enumerate() {
+            packed[first + i] |= word << shift;
+            if shift > 0 && first + i + 1 < packed.len() {
+                packed[first + i + 1] |= word >> (64 - shift);
+            }
+        }
+        let ambiguous = self.ambiguous.iter().copied()
+            .chain(other.ambiguous.iter().map(|(p, b)| (p + self.len, *b)))
+            .collect();
+        Self {
+            packed,
+            len,
+            n_runs: join_runs(&self.n_runs, &other.n_runs, self.len),
+            ambiguous,
+            masked: join_runs(&self.masked, &other.masked, self.len),
+        }
     }
 
     pub fn get_base(&self, index: usize) -> Result<Self> {
-        if index > self.seq.len() {return Err(anyhow!("index must be less than sequence length"))}
-        let base = self.seq[index..index+1].to_string();
-        let new_instance = Self::from_dna(base)
-            .expect("A Slice of an existing Seq should not throw any errors");
-        Ok(new_instance)
+        if index >= self.len {return Err(anyhow!("index must be less than sequence length"))}
+        self.get_range(index, index + 1)
     }
 
     pub fn rev_comp(&self) -> Seq {
-        let rc = Self::from_dna(
-            self.seq.chars().rev()
-                .map(|b| match b {
-                    'A' => 'T',
-                    'T' => 'A',
-                    'C' => 'G',
-                    'G' => 'C',
-                    'N' => 'N',
-                    _ => panic!("A non-DNA base was found in an existing Seq instance: {b}"),
-                })
-                .collect::<String>()
-        ).expect("Self should only contain legal bases.");
-        rc
+        // Complement every base and reverse the bases within each word, then
+        // reverse the words. The unused bases at the end of the last word end
+        // up at the start, so skip them.
+        let reversed: Vec<u64> = self.packed.iter().rev()
+            .map(|w| reverse_bases(!w))
+            .collect();
+        let padding = self.packed.len() * BASES_PER_WORD - self.len;
+        let mut packed = Self::extract(&reversed, padding, self.len);
+        let ambiguous: Vec<(usize, u8)> = self.ambiguous.iter().rev()
+            .map(|(p, b)| (self.len - 1 - p, complement(*b)))
+            .collect();
+        let n_runs = reverse_runs(&self.n_runs, self.len);
+        // Ambiguous bases are stored as A, which the complement turned into T
+        let n_positions = n_runs.iter().flat_map(|[start, end]| *start .. *end);
+        for p in n_positions.chain(ambiguous.iter().map(|(p, _)| *p)) {
+            packed[p / BASES_PER_WORD] &= !(0b11u64 << (2 * (p % BASES_PER_WORD)));
+        }
+        let masked = reverse_runs(&self.masked, self.len);
+        Self { packed, len: self.len, n_runs, ambiguous, masked }
+    }
+
+    /// Soft-masked runs of bases as 0-based, end-exclusive ranges, in order
+    pub fn masked_regions(&self) -> &[[usize; 2]] {
+        &self.masked
+    }
+
+    /// Soft-masked runs overlapping `start..stop`, clipped to it
+    pub fn masked_regions_in(&self, start: usize, stop: usize) -> Vec<[usize; 2]> {
+        runs_in(&self.masked, start, stop)
+    }
+
+    /// Whether the base at `index` is soft-masked
+    pub fn is_masked(&self, index: usize) -> bool {
+        in_runs(&self.masked, index)
+    }
+
+    /// Number of soft-masked bases in `start..stop`
+    pub fn masked_in(&self, start: usize, stop: usize) -> usize {
+        self.masked_regions_in(start, stop).iter().map(|[s, e]| e - s).sum()
     }
+
+    /// Bases with soft-masked runs in lowercase, as they were read
+    pub fn to_masked_string(&self) -> String {
+        let mut bytes = self.to_bytes();
+        for [start, end] in &self.masked {
+            bytes[*start .. *end].make_ascii_lowercase();
+        }
+        String::from_utf8(bytes).expect("Seq only contains ASCII bases")
+    }
+}
+
+// Runs of lowercase letters as 0-based, end-exclusive ranges
+fn lowercase_runs(bases: &[u8]) -> Vec<[usize; 2]> {
+    let mut runs: Vec<[usize; 2]> = Vec::new();
+    for (i, b) in bases.iter().enumerate() {
+        if b.is_ascii_lowercase() {
+            extend_runs(&mut runs, i);
+        }
+    }
+    runs
+}
+
+// Add position `i`, past the end of any existing run, to sorted runs
+fn extend_runs(runs: &mut Vec<[usize; 2]>, i: usize) {
+    match runs.last_mut() {
+        Some(run) if run[1] == i => run[1] = i + 1,
+        _ => runs.push([i, i + 1]),
+    }
+}
+
+// Sorted runs overlapping `start..stop`, clipped to it
+fn runs_in(runs: &[[usize; 2]], start: usize, stop: usize) -> Vec<[usize; 2]> {
+    let first = runs.partition_point(|[_, end]| *end <= start);
+    runs[first ..].iter()
+        .take_while(|[s, _]| *s < stop)
+        .map(|[s, e]| [(*s).max(start), (*e).min(stop)])
+        .collect()
+}
+
+fn in_runs(runs: &[[usize; 2]], index: usize) -> bool {
+    let run = runs.partition_point(|[_, end]| *end <= index);
+    runs.get(run).is_some_and(|[start, _]| *start <= index)
+}
+
+// Runs of two sequences joined end to end, where the first has length `offset`
+fn join_runs(first: &[[usize; 2]], second: &[[usize; 2]], offset: usize) -> Vec<[usize; 2]> {
+    let mut runs = first.to_vec();
+    for [start, end] in second.iter().map(|[s, e]| [s + offset, e + offset]) {
+        match runs.last_mut() {
+            Some(run) if run[1] == start => run[1] = end,
+            _ => runs.push([start, end]),
+        }
+    }
+    runs
+}
+
+// Runs of a sequence of length `len` on the other strand
+fn reverse_runs(runs: &[[usize; 2]], len: usize) -> Vec<[usize; 2]> {
+    runs.iter().rev()
+        .map(|[start, end]| [len - end, len - start])
+        .collect()
+}
+
+// Reverse the order of the 2-bit bases in a word
+fn reverse_bases(word: u64) -> u64 {
+    let w = ((word >> 2) & 0x3333_3333_3333_3333) | ((word & 0x3333_3333_3333_3333) << 2);
+    let w = ((w >> 4) & 0x0F0F_0F0F_0F0F_0F0F) | ((w & 0x0F0F_0F0F_0F0F_0F0F) << 4);
+    w.swap_bytes()
 }
 
 impl std::fmt::Display for Seq {
     fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
-        fmt.write_str(&self.seq)?;
+        let bases = String::from_utf8(self.to_bytes())
+            .expect("Seq only contains ASCII bases");
+        fmt.write_str(&bases)?;
         Ok(())
     }
 }
@@ -185,6 +539,13 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn header_is_circular_works() {
+        assert!(header_is_circular("contig_1 length=5000 circular=true"));
+        assert!(!header_is_circular("contig_1 length=5000 circular=false"));
+        assert!(!header_is_circular("contig_1"));
+    }
+
     #[test]
     fn seq_from_dna_strips_newline() {
         let result = Seq::from_dna("ATCG\nATCG".to_string()).unwrap();
@@ -198,6 +559,12 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn seq_from_dna_non_ascii_is_err() {
+        // ß uppercases to SS, and S is an ambiguity code
+        assert!(Seq::from_dna("AßA".to_string()).is_err());
+    }
+
     #[test]
     fn seq_from_dna_lowercase_converted() {
         let result = Seq::from_dna("atcgn".to_string()).unwrap();
@@ -226,4 +593,76 @@ mod tests {
         let expected = Seq::from_dna("T".to_string()).unwrap();
     }
 
+    #[test]
+    fn packed_seq_round_trips_across_words() {
+        let bases: String = (0..100).map(|i| ['A', 'C', 'G', 'T', 'N'][(i * 7 + i / 3) % 5]).collect();
+        let seq = Seq::from_dna(bases.clone()).unwrap();
+        assert_eq!(seq.len(), 100);
+        assert_eq!(seq.to_string(), bases);
+        assert_eq!(seq.get_range(30, 70).unwrap().to_string(), bases[30..70]);
+        assert_eq!(seq.get_range(30, 70).unwrap(), Seq::from_dna(bases[30..70].to_string()).unwrap());
+        let rc: String = bases.chars().rev()
+            .map(|b| match b { 'A' => 'T', 'T' => 'A', 'C' => 'G', 'G' => 'C', _ => 'N' })
+            .collect();
+        assert_eq!(seq.rev_comp(), Seq::from_dna(rc).unwrap());
+        assert_eq!(seq.rev_comp().rev_comp(), seq);
+        assert!(seq.contains_n_in(0, 5) == bases[0..5].contains('N'));
+    }
+
+    #[test]
+    fn concat_joins_packed_seqs() {
+        let a = Seq::from_dna(format!("{}ACgtNN", "T".repeat(33))).unwrap();
+        let b = Seq::from_dna(format!("NRcc{}", "G".repeat(40))).unwrap();
+        let joined = a.concat(&b);
+        assert_eq!(joined, Seq::from_dna(format!("{a}{b}")).unwrap());
+        assert_eq!(joined.to_masked_string(), format!("{}{}", a.to_masked_string(), b.to_masked_string()));
+        assert_eq!(joined.n_runs, vec![[37, 40]]);
+        assert_eq!(joined.masked_regions(), &[[35, 37], [41, 43]]);
+        assert_eq!(b.concat(&a).rev_comp(), a.rev_comp().concat(&b.rev_comp()));
+    }
+
+    #[test]
+    fn n_runs_are_stored_as_ranges() {
+        let seq = Seq::from_dna(format!("ACG{}TRA{}", "N".repeat(100), "N".repeat(5))).unwrap();
+        assert_eq!(seq.n_runs, vec![[3, 103], [106, 111]]);
+        assert_eq!(seq.ambiguous, vec![(104, b'R')]);
+        assert_eq!(seq.rev_comp().n_runs, vec![[0, 5], [8, 108]]);
+        assert_eq!(seq.get_range(50, 110).unwrap().n_runs, vec![[0, 53], [56, 60]]);
+        assert!(!seq.contains_n_in(103, 106) && seq.contains_ambiguous_in(103, 106));
+        assert_eq!(seq.rev_comp().rev_comp(), seq);
+    }
+
+    #[test]
+    fn seq_keeps_soft_masking() {
+        let seq = Seq::from_dna("ACgtaCG\nTacGT".to_string()).unwrap();
+        assert_eq!(seq.to_string(), "ACGTACGTACGT");
+        assert_eq!(seq.to_masked_string(), "ACgtaCGTacGT");
+        assert_eq!(seq.masked_regions(), &[[2, 5], [8, 10]]);
+        assert!(seq.is_masked(4) && !seq.is_masked(5));
+        assert_eq!(seq.masked_in(3, 9), 3);
+        assert_eq!(seq.get_range(3, 9).unwrap().to_masked_string(), "taCGTa");
+        assert_eq!(seq.rev_comp().to_masked_string(), "ACgtACGtacGT");
+        assert_eq!(seq, Seq::from_dna("ACGTACGTACGT".to_string()).unwrap());
+    }
+
+    #[test]
+    fn seq_keeps_and_complements_iupac_codes() {
+        let seq = Seq::from_dna("ACRYSWKMBDHVN".to_string()).unwrap();
+        assert_eq!(seq.to_string(), "ACRYSWKMBDHVN");
+        assert_eq!(seq.rev_comp().to_string(), "NBDHVKMWSRYGT");
+        assert_eq!(seq.rev_comp().rev_comp(), seq);
+        assert!(!seq.contains_n_in(0, 12));
+        assert!(seq.contains_ambiguous_in(2, 3));
+        assert_eq!(seq.slice(2, 4).unwrap().rev_comp().to_string(), "RY");
+    }
+
+    #[test]
+    fn ambiguity_policy_is_applied() {
+        let to_n = Seq::from_dna_with_policy("ACRTY".to_string(), AmbiguityPolicy::ToN).unwrap();
+        assert_eq!(to_n.to_string(), "ACNTN");
+        let rejected = Seq::from_dna_with_policy("ACGTAY".to_string(), AmbiguityPolicy::Reject);
+        assert!(rejected.unwrap_err().to_string().contains("Y found at position 6"));
+        assert!(Seq::from_dna_with_policy("ACGTN".to_string(), AmbiguityPolicy::Reject).is_ok());
+    }
+
 }
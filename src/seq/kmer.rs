@@ -3,9 +3,10 @@
 #![allow(unused_imports)]
 #![allow(unreachable_code)]
 
-use std::collections::{HashMap, VecDeque};
+use std::collections::HashMap;
 
 use crate::seq::fasta::{Fasta, Seq};
+use crate::seq::slice::SeqSlice;
 
 #[derive(Debug, PartialEq)]
 pub struct KmerTable {
@@ -36,34 +37,73 @@ impl KmerTable {
         }
     }
 
+    /// Index every k-mer in `s` by its start position. K-mers containing N
+    /// (e.g. scaffold gaps) or other ambiguity codes are skipped, as they
+    /// can't be seeded exactly.
     pub fn from_seq(s: &Seq, k: usize) -> Self {
+        Self::from_slice(s.as_slice(), k)
+    }
+
+    /// As `from_seq`, for a view of a sequence. K-mers of up to 32 bases are
+    /// rolled through a packed integer, so each distinct k-mer is only copied
+    /// out once however often it occurs.
+    pub fn from_slice(s: SeqSlice, k: usize) -> Self {
+        Self::index(s, k, false)
+    }
+
+    /// As `from_seq`, also skipping k-mers that include soft-masked bases
+    pub fn from_seq_unmasked(s: &Seq, k: usize) -> Self {
+        Self::from_slice_unmasked(s.as_slice(), k)
+    }
+
+    /// As `from_slice`, also skipping k-mers that include soft-masked bases
+    pub fn from_slice_unmasked(s: SeqSlice, k: usize) -> Self {
+        Self::index(s, k, true)
+    }
+
+    fn index(s: SeqSlice, k: usize, skip_masked: bool) -> Self {
         let mut kt = Self::new();
-        let mut vd = VecDeque::with_capacity(k);
-        // Initial kmer
-        for i in 0..k {
-            vd.push_back(s.get_base(i));
+        if k == 0 || s.len() < k {
+            return kt
+        }
+        if k > 32 {
+            for start in 0 ..= s.len() - k {
+                let kmer = s.slice(start, start + k).expect("K-mer is within the sequence");
+                let masked = skip_masked && (0 .. k).any(|i| kmer.is_masked(i));
+                if !kmer.contains_ambiguous() && !masked {
+                    kt.add(kmer.to_seq(), start);
+                }
+            }
+            return kt
+        }
+
+        let mask = match k {
+            32 => u64::MAX,
+            _ => (1u64 << (2 * k)) - 1,
         };
-        let seq = Seq::from_dna(
-                vd.iter().map(|x| x.as_ref().expect("Seq should only yield valid seq").to_string())
-                .collect()
-            ).expect("Combining bases from Seq should yield valid Seq");
-        let mut k_start_pos = 0usize;
-        kt.add(seq, k_start_pos);
-        for new_base_pos in k..s.len() {
-            k_start_pos += 1;
-            vd.pop_front();
-            vd.push_back(s.get_base(new_base_pos));
-            let seq = Seq::from_dna(
-                    vd.iter().map(|x| x.as_ref().expect("Seq should only yield valid seq").to_string())
-                    .collect()
-                ).expect("Combining bases from Seq should yield valid Seq");
-            kt.add(seq, k_start_pos);
+        let mut packed: HashMap<u64, Vec<usize>> = HashMap::new();
+        let mut code = 0u64;
+        let mut since_n = 0usize; // bases since the last N, ambiguity code or skipped masked base
+        for i in 0 .. s.len() {
+            match s.code(i).filter(|_| !(skip_masked && s.is_masked(i))) {
+                Some(c) => {
+                    code = ((code << 2) | c as u64) & mask;
+                    since_n += 1;
+                },
+                None => since_n = 0,
+            }
+            if since_n >= k {
+                packed.entry(code).or_default().push(i + 1 - k);
+            }
+        }
+        for locs in packed.into_values() {
+            let kmer = s.slice(locs[0], locs[0] + k).expect("K-mer is within the sequence");
+            kt.kmers.insert(kmer.to_seq(), locs);
         }
         kt
     }
 }
 
-
 impl Default for KmerTable {
     fn default() -> Self {
         Self::new()
@@ -135,4 +175,38 @@ mod tests {
         assert_eq!(result, expected)
 
     }
+
+    #[test]
+    fn kmer_from_slice_reads_view() {
+        let s = Seq::from_dna("GGATCGATCGGG".to_string()).unwrap();
+        let result = KmerTable::from_slice(s.slice(2, 10).unwrap().rev_comp(), 4usize);
+        let expected = KmerTable::from_seq(&Seq::from_dna("CGATCGAT".to_string()).unwrap(), 4usize);
+        assert_eq!(result, expected)
+    }
+
+    #[test]
+    fn kmer_from_seq_skips_n() {
+        let s = Seq::from_dna("ATCGNNATCG".to_string()).unwrap();
+        let result = KmerTable::from_seq(&s, 4usize);
+        let expected = KmerTable{
+            kmers: HashMap::from([
+                (Seq::from_dna("ATCG".to_string()).unwrap(), vec![0usize, 6usize]),
+            ])
+        };
+        assert_eq!(result, expected)
+    }
+
+    #[test]
+    fn kmer_from_seq_unmasked_skips_masked() {
+        let s = Seq::from_dna("ATCGATcgATCGA".to_string()).unwrap();
+        let result = KmerTable::from_seq_unmasked(&s, 4usize);
+        let expected = KmerTable{
+            kmers: HashMap::from([
+                (Seq::from_dna("ATCG".to_string()).unwrap(), vec![0usize, 8usize]),
+                (Seq::from_dna("TCGA".to_string()).unwrap(), vec![1usize, 9usize]),
+                (Seq::from_dna("CGAT".to_string()).unwrap(), vec![2usize]),
+            ])
+        };
+        assert_eq!(result, expected)
+    }
 }
@@ -0,0 +1,211 @@
+use std::borrow::Cow;
+use std::fmt;
+
+use anyhow::{anyhow, Result};
+
+use crate::seq::fasta::{complement, Seq};
+
+
+/// A borrowed view of part of a `Seq`, optionally reverse complemented.
+/// Slicing and reverse complementing a view only adjust its bounds, so nothing
+/// is copied until `to_seq` is called.
+#[derive(Debug, Clone, Copy)]
+pub struct SeqSlice<'a> {
+    seq: &'a Seq,
+    start: usize,
+    len: usize,
+    reverse: bool,
+}
+
+// constructors
+impl<'a> SeqSlice<'a> {
+    /// View of `start..stop` of `seq` on the forward strand
+    pub fn new(seq: &'a Seq, start: usize, stop: usize) -> Result<Self> {
+        if start > stop {return Err(anyhow!("start index must not be greater than stop index"))}
+        if stop > seq.len() {return Err(anyhow!("stop index must be less than or equal to sequence length"))}
+        Ok(Self { seq, start, len: stop - start, reverse: false })
+    }
+}
+
+// methods
+impl<'a> SeqSlice<'a> {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether the view reads the reverse complement of the underlying sequence
+    pub fn is_reverse(&self) -> bool {
+        self.reverse
+    }
+
+    /// 0-based start and (exclusive) end of the view in the underlying sequence
+    pub fn bounds(&self) -> [usize; 2] {
+        [self.start, self.start + self.len]
+    }
+
+    // Position in the underlying sequence of base `index` of the view
+    fn source_index(&self, index: usize) -> usize {
+        match self.reverse {
+            false => self.start + index,
+            true => self.start + self.len - 1 - index,
+        }
+    }
+
+    /// 2-bit code (A=0, C=1, G=2, T=3) of the base at `index`, or `None` for N,
+    /// other ambiguity codes or an index past the end
+    pub fn code(&self, index: usize) -> Option<u8> {
+        if index >= self.len {
+            return None
+        }
+        let code = self.seq.code_at(self.source_index(index))?;
+        Some(match self.reverse {
+            false => code,
+            true => 3 - code,
+        })
+    }
+
+    /// Uppercase ASCII base or ambiguity code at `index`
+    pub fn get(&self, index: usize) -> Option<u8> {
+        if index >= self.len {
+            return None
+        }
+        let base = self.seq.base_at(self.source_index(index));
+        Some(match self.reverse {
+            false => base,
+            true => complement(base),
+        })
+    }
+
+    /// Uppercase ASCII bases of the view in order
+    pub fn iter(&self) -> impl Iterator<Item = u8> + '_ {
+        (0 .. self.len).map(|i| self.get(i).expect("Index is within the view"))
+    }
+
+    /// View of `start..stop` of this view
+    pub fn slice(&self, start: usize, stop: usize) -> Result<SeqSlice<'a>> {
+        if start > stop {return Err(anyhow!("start index must not be greater than stop index"))}
+        if stop > self.len {return Err(anyhow!("stop index must be less than or equal to slice length"))}
+        let start_in_seq = match self.reverse {
+            false => self.start + start,
+            true => self.start + self.len - stop,
+        };
+        Ok(Self { start: start_in_seq, len: stop - start, ..*self })
+    }
+
+    /// The same bases read from the other strand
+    pub fn rev_comp(&self) -> SeqSlice<'a> {
+        Self { reverse: !self.reverse, ..*self }
+    }
+
+    pub fn contains_n(&self) -> bool {
+        self.seq.contains_n_in(self.start, self.start + self.len)
+    }
+
+    /// Whether the base at `index` is soft-masked
+    pub fn is_masked(&self, index: usize) -> bool {
+        index < self.len && self.seq.is_masked(self.source_index(index))
+    }
+
+    /// Whether the view contains N or any other ambiguity code
+    pub fn contains_ambiguous(&self) -> bool {
+        self.seq.contains_ambiguous_in(self.start, self.start + self.len)
+    }
+
+    /// Copy the viewed bases into a new `Seq`
+    pub fn to_seq(&self) -> Seq {
+        if self.is_empty() {
+            return Seq::from_dna(String::new()).expect("Empty sequence is valid")
+        }
+        let seq = self.seq.get_range(self.start, self.start + self.len)
+            .expect("View is within the sequence");
+        match self.reverse {
+            false => seq,
+            true => seq.rev_comp(),
+        }
+    }
+
+    /// The viewed bases as a `Seq`, borrowing the underlying sequence when
+    /// the view is all of it on the forward strand and copying otherwise
+    pub fn to_cow(&self) -> Cow<'a, Seq> {
+        match !self.reverse && self.start == 0 && self.len == self.seq.len() {
+            true => Cow::Borrowed(self.seq),
+            false => Cow::Owned(self.to_seq()),
+        }
+    }
+
+    /// Uppercase ASCII bases, for code that needs to compare positions directly
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let bytes = self.seq.bytes_in(self.start, self.start + self.len);
+        match self.reverse {
+            false => bytes,
+            true => bytes.into_iter().rev().map(complement).collect(),
+        }
+    }
+}
+
+impl<'a> From<&'a Seq> for SeqSlice<'a> {
+    fn from(seq: &'a Seq) -> Self {
+        seq.as_slice()
+    }
+}
+
+impl PartialEq for SeqSlice<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && (0 .. self.len).all(|i| self.get(i) == other.get(i))
+    }
+}
+
+impl Eq for SeqSlice<'_> {}
+
+impl PartialEq<Seq> for SeqSlice<'_> {
+    fn eq(&self, other: &Seq) -> bool {
+        *self == other.as_slice()
+    }
+}
+
+impl fmt::Display for SeqSlice<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let bases = String::from_utf8(self.to_bytes())
+            .expect("Seq only contains ASCII bases");
+        fmt.write_str(&bases)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slice_views_without_copying() {
+        let seq = Seq::from_dna("AACGTNGGT".to_string()).unwrap();
+        let view = seq.slice(1, 8).unwrap();
+        assert_eq!(view.to_string(), "ACGTNGG");
+        assert_eq!(view.get(5), Some(b'G'));
+        assert_eq!(view.get(7), None);
+        assert!(view.contains_n());
+        assert_eq!(view.slice(1, 3).unwrap().to_string(), "CG");
+
+        let rc = view.rev_comp();
+        assert_eq!(rc.to_string(), "CCNACGT");
+        assert_eq!(rc.slice(0, 2).unwrap().to_string(), "CC");
+        assert_eq!(rc.slice(3, 7).unwrap().bounds(), [1, 5]);
+        assert_eq!(rc.to_seq(), seq.get_range(1, 8).unwrap().rev_comp());
+        assert_eq!(rc.rev_comp(), view);
+        assert!(matches!(seq.as_slice().to_cow(), Cow::Borrowed(_)));
+        assert!(matches!(view.to_cow(), Cow::Owned(_)));
+    }
+
+    #[test]
+    fn slices_compare_by_bases() {
+        let seq = Seq::from_dna("ACGTACGT".to_string()).unwrap();
+        assert_eq!(seq.slice(0, 4).unwrap(), seq.slice(4, 8).unwrap());
+        assert_ne!(seq.slice(0, 4).unwrap(), seq.slice(1, 5).unwrap());
+        // ACGT is its own reverse complement
+        assert_eq!(seq.slice(0, 4).unwrap().rev_comp(), Seq::from_dna("ACGT".to_string()).unwrap());
+    }
+}
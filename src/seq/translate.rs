@@ -0,0 +1,257 @@
+use std::fmt;
+
+use anyhow::{anyhow, Result};
+
+use crate::seq::slice::SeqSlice;
+
+// Amino acids for each NCBI translation table, indexed by codon with bases in
+// TCAG order (TTT, TTC, TTA, TTG, TCT, ...) as NCBI lists them. Stops are '*'.
+// Codons that are stops only at the end of a gene in tables 27, 28 and 31 are
+// translated as their amino acid, as in the NCBI tables
+const TABLES: [(u8, &str, &[u8; 64]); 25] = [
+    (1, "Standard", b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (2, "Vertebrate Mitochondrial", b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSS**VVVVAAAADDEEGGGG"),
+    (3, "Yeast Mitochondrial", b"FFLLSSSSYY**CCWWTTTTPPPPHHQQRRRRIIMMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (4, "Mold, Protozoan, and Coelenterate Mitochondrial and Mycoplasma/Spiroplasma", b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (5, "Invertebrate Mitochondrial", b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSSSVVVVAAAADDEEGGGG"),
+    (6, "Ciliate, Dasycladacean and Hexamita Nuclear", b"FFLLSSSSYYQQCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (9, "Echinoderm and Flatworm Mitochondrial", b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG"),
+    (10, "Euplotid Nuclear", b"FFLLSSSSYY**CCCWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (11, "Bacterial, Archaeal and Plant Plastid", b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (12, "Alternative Yeast Nuclear", b"FFLLSSSSYY**CC*WLLLSPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (13, "Ascidian Mitochondrial", b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSGGVVVVAAAADDEEGGGG"),
+    (14, "Alternative Flatworm Mitochondrial", b"FFLLSSSSYYY*CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG"),
+    (16, "Chlorophycean Mitochondrial", b"FFLLSSSSYY*LCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (21, "Trematode Mitochondrial", b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNNKSSSSVVVVAAAADDEEGGGG"),
+    (22, "Scenedesmus obliquus Mitochondrial", b"FFLLSS*SYY*LCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (23, "Thraustochytrium Mitochondrial", b"FF*LSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (24, "Rhabdopleuridae Mitochondrial", b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSSKVVVVAAAADDEEGGGG"),
+    (25, "Candidate Division SR1 and Gracilibacteria", b"FFLLSSSSYY**CCGWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (26, "Pachysolen tannophilus Nuclear", b"FFLLSSSSYY**CC*WLLLAPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (27, "Karyorelict Nuclear", b"FFLLSSSSYYQQCCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (28, "Condylostoma Nuclear", b"FFLLSSSSYYQQCCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (29, "Mesodinium Nuclear", b"FFLLSSSSYYYYCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (30, "Peritrich Nuclear", b"FFLLSSSSYYEECC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (31, "Blastocrithidia Nuclear", b"FFLLSSSSYYEECCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (33, "Cephalodiscidae Mitochondrial", b"FFLLSSSSYYY*CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSSKVVVVAAAADDEEGGGG"),
+];
+
+// Position in TCAG order of each 2-bit base code (A=0, C=1, G=2, T=3)
+const TCAG_INDEX: [usize; 4] = [2, 1, 3, 0];
+
+const RESIDUES: &str = "ACDEFGHIKLMNPQRSTVWYX*";
+
+
+/// An NCBI translation table, e.g. 11 for bacteria and archaea or 4 for Mycoplasma
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GeneticCode {
+    id: u8,
+    name: &'static str,
+    amino_acids: &'static [u8; 64],
+}
+
+// constructors
+impl GeneticCode {
+    /// The table with NCBI number `id`
+    pub fn from_id(id: u8) -> Result<Self> {
+        TABLES.iter()
+            .find(|(table, _, _)| *table == id)
+            .map(|(id, name, amino_acids)| Self { id: *id, name, amino_acids })
+            .ok_or_else(|| anyhow!("unknown translation table {id}"))
+    }
+}
+
+// methods
+impl GeneticCode {
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        self.name
+    }
+
+    /// Amino acid coded for by three 2-bit base codes, or X if any base is N
+    /// or another ambiguity code
+    fn amino_acid(&self, codon: [Option<u8>; 3]) -> u8 {
+        match codon {
+            [Some(a), Some(b), Some(c)] => {
+                let index = 16 * TCAG_INDEX[a as usize] + 4 * TCAG_INDEX[b as usize] + TCAG_INDEX[c as usize];
+                self.amino_acids[index]
+            },
+            _ => b'X',
+        }
+    }
+}
+
+/// Bacteria, archaea and plastids
+impl Default for GeneticCode {
+    fn default() -> Self {
+        Self::from_id(11).expect("Table 11 is defined")
+    }
+}
+
+
+/// One of the six reading frames. Forward frames start at the first, second
+/// or third base; reverse frames read the reverse complement starting at the
+/// last, second last or third last base.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Frame {
+    Plus1,
+    Plus2,
+    Plus3,
+    Minus1,
+    Minus2,
+    Minus3,
+}
+
+impl Frame {
+    pub const ALL: [Frame; 6] = [Self::Plus1, Self::Plus2, Self::Plus3, Self::Minus1, Self::Minus2, Self::Minus3];
+
+    /// Bases skipped before the first codon, on the strand the frame reads
+    pub fn offset(&self) -> usize {
+        match self {
+            Self::Plus1 | Self::Minus1 => 0,
+            Self::Plus2 | Self::Minus2 => 1,
+            Self::Plus3 | Self::Minus3 => 2,
+        }
+    }
+
+    pub fn is_reverse(&self) -> bool {
+        matches!(self, Self::Minus1 | Self::Minus2 | Self::Minus3)
+    }
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = match self.is_reverse() {
+            true => '-',
+            false => '+',
+        };
+        write!(f, "{sign}{}", self.offset() + 1)
+    }
+}
+
+
+/// Protein sequence of one-letter amino acid codes, with X for unknown
+/// residues and * for stops
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct Protein {
+    residues: Vec<u8>,
+}
+
+// constructors
+impl Protein {
+    pub fn from_residues(residues: String) -> Result<Self> {
+        let residues = residues.to_uppercase();
+        if !residues.chars().all(|r| RESIDUES.contains(r)) {
+            return Err(anyhow!("non-amino acid residue found in protein sequence"))
+        }
+        Ok(Self { residues: residues.into_bytes() })
+    }
+}
+
+// methods
+impl Protein {
+    pub fn len(&self) -> usize {
+        self.residues.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.residues.is_empty()
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.residues
+    }
+
+    /// Positions of stop codons
+    pub fn stops(&self) -> Vec<usize> {
+        self.residues.iter().enumerate()
+            .filter(|(_, r)| **r == b'*')
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
+
+impl fmt::Display for Protein {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let residues = String::from_utf8(self.residues.clone())
+            .expect("Protein only contains ASCII residues");
+        fmt.write_str(&residues)
+    }
+}
+
+
+/// Translate `source`, a `Seq` or a view of part of one, in `frame` using
+/// `code`. Bases left over after the last whole codon are ignored.
+pub fn translate<'a>(source: impl Into<SeqSlice<'a>>, frame: Frame, code: &GeneticCode) -> Protein {
+    let source = source.into();
+    let strand = match frame.is_reverse() {
+        true => source.rev_comp(),
+        false => source,
+    };
+    let n_codons = strand.len().saturating_sub(frame.offset()) / 3;
+    let residues = (0 .. n_codons)
+        .map(|c| {
+            let first = frame.offset() + 3 * c;
+            code.amino_acid([strand.code(first), strand.code(first + 1), strand.code(first + 2)])
+        })
+        .collect();
+    Protein { residues }
+}
+
+/// Translate `source` in all six frames, in the order of `Frame::ALL`
+pub fn six_frames<'a>(source: impl Into<SeqSlice<'a>>, code: &GeneticCode) -> Vec<(Frame, Protein)> {
+    let source = source.into();
+    Frame::ALL.iter()
+        .map(|frame| (*frame, translate(source, *frame, code)))
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seq::fasta::Seq;
+
+    #[test]
+    fn translates_six_frames() {
+        let seq = Seq::from_dna("ATGGCCTGAAAGTTTAA".to_string()).unwrap();
+        let proteins: Vec<String> = six_frames(&seq, &GeneticCode::default()).into_iter()
+            .map(|(_, p)| p.to_string())
+            .collect();
+        assert_eq!(proteins, vec!["MA*KF", "WPESL", "GLKV*", "LNFQA", "*TFRP", "KLSGH"]);
+        assert_eq!(Frame::Minus2.to_string(), "-2");
+
+        // TGA codes for tryptophan in Mycoplasma
+        let mycoplasma = GeneticCode::from_id(4).unwrap();
+        assert_eq!(translate(seq.slice(0, 9).unwrap(), Frame::Plus1, &mycoplasma).to_string(), "MAW");
+        assert_eq!(translate(seq.slice(0, 9).unwrap(), Frame::Plus1, &GeneticCode::default()).stops(), vec![2]);
+    }
+
+    #[test]
+    fn genetic_codes_are_complete() {
+        // Every table NCBI defines. 7, 8, 15, 17-20 and 32 are unused
+        let ncbi_ids = [1, 2, 3, 4, 5, 6, 9, 10, 11, 12, 13, 14, 16, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 33];
+        let ids: Vec<u8> = TABLES.iter().map(|(id, _, _)| *id).collect();
+        assert_eq!(ids, ncbi_ids);
+        assert!(ncbi_ids.iter().all(|id| GeneticCode::from_id(*id).is_ok()));
+        for id in [0, 7, 8, 15, 17, 18, 19, 20, 32, 34] {
+            assert!(GeneticCode::from_id(id).is_err());
+        }
+        assert!(TABLES.iter().all(|(_, _, aas)| aas.iter().all(|a| RESIDUES.as_bytes().contains(a))));
+        // Each table's changes from the standard code
+        let standard = GeneticCode::from_id(1).unwrap();
+        let codons = Seq::from_dna("TCATTAAGGTAGCTG".to_string()).unwrap();
+        let changed: Vec<String> = [1, 16, 22, 23, 24, 26].iter()
+            .map(|id| translate(&codons, Frame::Plus1, &GeneticCode::from_id(*id).unwrap()).to_string())
+            .collect();
+        assert_eq!(changed, vec!["SLR*L", "SLRLL", "*LRLL", "S*R*L", "SLK*L", "SLR*A"]);
+        assert_eq!(standard.name(), "Standard");
+        let seq = Seq::from_dna("ATGNCCATR".to_string()).unwrap();
+        assert_eq!(translate(&seq, Frame::Plus1, &GeneticCode::default()).to_string(), "MXX");
+        assert!(Protein::from_residues("MKV*".to_string()).is_ok());
+        assert!(Protein::from_residues("MKB".to_string()).is_err());
+    }
+}